@@ -1,2 +1,2 @@
 cargo build
-halite.exe --replay-directory replays/ -vvv --width 32 --height 32 "target\debug\my_bot" "target\debug\my_bot"
+target\debug\runner.exe --replay-directory replays/ --sizes 32 "target\debug\my_bot" "target\debug\my_bot"
@@ -0,0 +1,241 @@
+use hlt::clock::Budget;
+use hlt::game::Game;
+use hlt::mining::MiningPlanner;
+use hlt::mining::MiningTarget;
+use hlt::position::Position;
+use hlt::ship::Ship;
+use hlt::ShipId;
+use std::collections::HashMap;
+
+// The smallest price increase `auction` accepts.
+const MIN_EPSILON: f64 = 1e-6;
+
+// Gives every ship a different mining target so that the total value per turn of the fleet is as
+// high as possible. Ships whose every candidate went to someone else get no target, as do ships
+// whose candidates there was no time left to look for or to assign once the budget ran out.
+//
+// Each ship keeps its best `candidates_per_ship` cells; cells outside a ship's own candidates are
+// worth nothing to it. Fleets larger than `auction_fleet_size` are assigned with the auction
+// algorithm, which scales better than the Hungarian algorithm's cubic running time at the cost
+// of being within `auction_epsilon` per ship of the optimum.
+pub fn assign_targets(game: &Game, planner: &MiningPlanner, ships: &[&Ship], budget: &Budget) -> HashMap<ShipId, MiningTarget> {
+    let mut cells: Vec<Position> = Vec::new();
+    let mut cell_indices: HashMap<Position, usize> = HashMap::new();
+    let mut candidates: Vec<Vec<(usize, MiningTarget)>> = Vec::with_capacity(ships.len());
+
+    for ship in ships {
+        let mut ship_candidates = Vec::new();
+        if budget.is_exhausted() {
+            candidates.push(ship_candidates);
+            continue;
+        }
+        for target in planner.targets(game, ship).into_iter().take(game.config.candidates_per_ship) {
+            let next_index = cells.len();
+            let cell_index = *cell_indices.entry(target.position).or_insert(next_index);
+            if cell_index == next_index {
+                cells.push(target.position);
+            }
+            ship_candidates.push((cell_index, target));
+        }
+        candidates.push(ship_candidates);
+    }
+
+    let values: Vec<Vec<(usize, f64)>> = candidates.iter()
+        .map(|ship_candidates| ship_candidates.iter().map(|&(cell, target)| (cell, target.value_per_turn)).collect())
+        .collect();
+    let assignment = if ships.len() > game.config.auction_fleet_size {
+        auction(&values, cells.len(), game.config.auction_epsilon, budget)
+    } else {
+        let mut matrix = vec![vec![0.0; cells.len()]; ships.len()];
+        for (row, ship_values) in values.iter().enumerate() {
+            for &(cell, value) in ship_values {
+                matrix[row][cell] = value;
+            }
+        }
+        hungarian(&matrix, budget)
+    };
+
+    let mut targets = HashMap::new();
+    for (row, ship) in ships.iter().enumerate() {
+        if let Some(cell) = assignment[row] {
+            if let Some(&(_, target)) = candidates[row].iter().find(|&&(candidate, _)| candidate == cell) {
+                targets.insert(ship.id, target);
+            }
+        }
+    }
+    targets
+}
+
+// Maximum value assignment of rows to distinct columns with the Hungarian algorithm, in
+// O(rows^2 * columns) time. Every row gets a column while there are columns left; rows beyond
+// the number of columns get None. Rows are added one at a time, and once the budget is exhausted
+// the rows not added yet get None while the ones already added keep the best columns among them.
+pub fn hungarian(values: &[Vec<f64>], budget: &Budget) -> Vec<Option<usize>> {
+    let rows = values.len();
+    let columns = values.first().map_or(0, |row| row.len());
+    if rows == 0 || columns == 0 {
+        return vec![None; rows];
+    }
+
+    // The algorithm below wants at least as many columns as rows, so pad with worthless columns.
+    let width = columns.max(rows);
+    let cost = |row: usize, column: usize| if column < columns { -values[row][column] } else { 0.0 };
+
+    // Potentials and matching are 1-indexed with index 0 as a sentinel.
+    let mut row_potential = vec![0.0; rows + 1];
+    let mut column_potential = vec![0.0; width + 1];
+    let mut column_row = vec![0usize; width + 1];
+    let mut way = vec![0usize; width + 1];
+
+    for row in 1..rows + 1 {
+        if budget.is_exhausted() {
+            break;
+        }
+        column_row[0] = row;
+        let mut column = 0;
+        let mut min_slack = vec![f64::INFINITY; width + 1];
+        let mut used = vec![false; width + 1];
+
+        loop {
+            used[column] = true;
+            let current_row = column_row[column];
+            let mut delta = f64::INFINITY;
+            let mut next_column = 0;
+            for candidate in 1..width + 1 {
+                if used[candidate] {
+                    continue;
+                }
+                let slack = cost(current_row - 1, candidate - 1) - row_potential[current_row] - column_potential[candidate];
+                if slack < min_slack[candidate] {
+                    min_slack[candidate] = slack;
+                    way[candidate] = column;
+                }
+                if min_slack[candidate] < delta {
+                    delta = min_slack[candidate];
+                    next_column = candidate;
+                }
+            }
+            for candidate in 0..width + 1 {
+                if used[candidate] {
+                    row_potential[column_row[candidate]] += delta;
+                    column_potential[candidate] -= delta;
+                } else {
+                    min_slack[candidate] -= delta;
+                }
+            }
+            column = next_column;
+            if column_row[column] == 0 {
+                break;
+            }
+        }
+
+        while column != 0 {
+            let previous = way[column];
+            column_row[column] = column_row[previous];
+            column = previous;
+        }
+    }
+
+    let mut assignment = vec![None; rows];
+    for column in 1..columns + 1 {
+        if column_row[column] != 0 {
+            assignment[column_row[column] - 1] = Some(column - 1);
+        }
+    }
+    assignment
+}
+
+// Maximum value assignment by the auction algorithm. Each row lists the columns it values; any
+// row may also stay unassigned for a value of zero, so only columns worth more than nothing are
+// ever taken. The result is within `epsilon` per row of the optimum. The bidding only ends because
+// every bid raises a price by at least epsilon, so smaller values are raised to `MIN_EPSILON`. Once
+// the budget is exhausted the rows holding a column when it ran out keep it and the rest get None.
+pub fn auction(values: &[Vec<(usize, f64)>], columns: usize, epsilon: f64, budget: &Budget) -> Vec<Option<usize>> {
+    let epsilon = epsilon.max(MIN_EPSILON);
+    let mut prices = vec![0.0; columns];
+    let mut owner: Vec<Option<usize>> = vec![None; columns];
+    let mut assignment: Vec<Option<usize>> = vec![None; values.len()];
+    let mut unassigned: Vec<usize> = (0..values.len()).rev().collect();
+
+    while let Some(row) = unassigned.pop() {
+        if budget.is_exhausted() {
+            break;
+        }
+
+        // Staying unassigned is always worth zero, which bounds the second best offer.
+        let mut best: Option<(usize, f64)> = None;
+        let mut second_best = 0.0;
+        for &(column, value) in &values[row] {
+            let profit = value - prices[column];
+            match best {
+                Some((_, best_profit)) if profit <= best_profit => {
+                    if profit > second_best {
+                        second_best = profit;
+                    }
+                },
+                _ => {
+                    if let Some((_, best_profit)) = best {
+                        if best_profit > second_best {
+                            second_best = best_profit;
+                        }
+                    }
+                    best = Some((column, profit));
+                },
+            }
+        }
+
+        let (column, best_profit) = match best {
+            Some((column, profit)) if profit > 0.0 => (column, profit),
+            _ => continue,
+        };
+
+        prices[column] += best_profit - second_best + epsilon;
+        if let Some(previous) = owner[column] {
+            assignment[previous] = None;
+            unassigned.push(previous);
+        }
+        owner[column] = Some(row);
+        assignment[row] = Some(column);
+    }
+
+    assignment
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hlt::clock::TurnClock;
+
+    #[test]
+    fn hungarian_finds_the_best_total_over_the_greedy_choice() {
+        // Row 0 greedily takes column 0, but the best total gives it column 1.
+        let values = vec![vec![10.0, 9.0], vec![8.0, 1.0]];
+        let assignment = hungarian(&values, &TurnClock::new().turn_budget());
+        assert_eq!(assignment, vec![Some(1), Some(0)]);
+    }
+
+    #[test]
+    fn hungarian_leaves_rows_beyond_the_columns_unassigned() {
+        let values = vec![vec![1.0], vec![5.0], vec![3.0]];
+        let assignment = hungarian(&values, &TurnClock::new().turn_budget());
+        assert_eq!(assignment, vec![None, Some(0), None]);
+    }
+
+    #[test]
+    fn auction_matches_the_optimum_on_sparse_values() {
+        let values = vec![vec![(0, 10.0), (1, 9.0)], vec![(0, 8.0)], vec![(2, 0.0)]];
+        let assignment = auction(&values, 3, 0.01, &TurnClock::new().turn_budget());
+        assert_eq!(assignment, vec![Some(1), Some(0), None]);
+    }
+
+    #[test]
+    fn auction_ends_even_without_a_positive_epsilon() {
+        // With no minimum increase both rows would keep outbidding each other for column 0.
+        let values = vec![vec![(0, 10.0), (1, 10.0)], vec![(0, 10.0), (1, 10.0)]];
+        for &epsilon in &[0.0, -1.0, f64::NAN] {
+            let mut assignment = auction(&values, 2, epsilon, &TurnClock::new().turn_budget());
+            assignment.sort();
+            assert_eq!(assignment, vec![Some(0), Some(1)]);
+        }
+    }
+}
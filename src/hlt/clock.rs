@@ -0,0 +1,150 @@
+use std::cell::Cell;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::time::Duration;
+use std::time::Instant;
+
+// The engine gives bots 2 seconds per turn, counted from when it sends the frame.
+const TURN_TIME_LIMIT_MS: u64 = 2000;
+// Kept back from the limit for writing the commands and for the engine reading them.
+const SAFETY_MARGIN_MS: u64 = 300;
+
+// A point in time planners should be done by. Planners that can stop early and still return
+// something useful poll it and settle for what they have once it is exhausted.
+#[derive(Copy, Clone)]
+pub struct Budget<'a> {
+    clock: &'a TurnClock,
+    deadline: Instant,
+}
+
+impl<'a> Budget<'a> {
+    pub fn is_exhausted(&self) -> bool {
+        self.clock.poll(self.deadline)
+    }
+}
+
+// Measures the time spent on the current turn. `Game` starts it as soon as the first line of a
+// frame is read, so the time spent parsing the frame counts too.
+//
+// Every poll of a budget is numbered within its turn and the ones that found it exhausted are
+// kept, so a replay can hand out the same outcomes instead of reading the time. Since the bot
+// makes the same polls in the same order until one comes out differently, that replays every
+// cutoff the original game hit.
+pub struct TurnClock {
+    started: Instant,
+    deadline: Duration,
+    turn_number: usize,
+    polls: Cell<usize>,
+    exhausted_polls: RefCell<Vec<usize>>,
+    // The exhausted polls of each turn of an earlier game, when replaying one.
+    replayed: Option<HashMap<usize, Vec<usize>>>,
+}
+
+impl TurnClock {
+    pub fn new() -> TurnClock {
+        TurnClock::with_deadline(Duration::from_millis(TURN_TIME_LIMIT_MS - SAFETY_MARGIN_MS))
+    }
+
+    pub fn with_deadline(deadline: Duration) -> TurnClock {
+        TurnClock {
+            started: Instant::now(),
+            deadline,
+            turn_number: 0,
+            polls: Cell::new(0),
+            exhausted_polls: RefCell::new(Vec::new()),
+            replayed: None,
+        }
+    }
+
+    // Budgets stop reading the time and come out exhausted exactly on the polls listed for the
+    // turn, by turn number.
+    pub fn replay(&mut self, exhausted_polls: HashMap<usize, Vec<usize>>) {
+        self.replayed = Some(exhausted_polls);
+    }
+
+    pub fn start(&mut self, turn_number: usize) {
+        self.started = Instant::now();
+        self.turn_number = turn_number;
+        self.polls.set(0);
+        self.exhausted_polls.borrow_mut().clear();
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    pub fn remaining(&self) -> Duration {
+        self.deadline.saturating_sub(self.elapsed())
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.elapsed() >= self.deadline
+    }
+
+    // The polls this turn that found their budget exhausted, in the order they were made.
+    pub fn exhausted_polls(&self) -> Vec<usize> {
+        self.exhausted_polls.borrow().clone()
+    }
+
+    // A budget covering `fraction` of the time left this turn, leaving the rest for whatever
+    // comes after.
+    pub fn budget(&self, fraction: f64) -> Budget<'_> {
+        Budget { clock: self, deadline: Instant::now() + self.remaining().mul_f64(fraction.clamp(0.0, 1.0)) }
+    }
+
+    // The whole of the time left this turn.
+    pub fn turn_budget(&self) -> Budget<'_> {
+        Budget { clock: self, deadline: self.started + self.deadline }
+    }
+
+    fn poll(&self, deadline: Instant) -> bool {
+        let poll = self.polls.get();
+        self.polls.set(poll + 1);
+
+        let exhausted = match &self.replayed {
+            Some(replayed) => replayed.get(&self.turn_number).is_some_and(|polls| polls.contains(&poll)),
+            None => Instant::now() >= deadline,
+        };
+        if exhausted {
+            self.exhausted_polls.borrow_mut().push(poll);
+        }
+        exhausted
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replayed_clock_exhausts_the_recorded_polls_only() {
+        let mut clock = TurnClock::with_deadline(Duration::from_secs(0));
+        let mut replayed = HashMap::new();
+        replayed.insert(3, vec![1, 2]);
+        clock.replay(replayed);
+
+        clock.start(3);
+        let budget = clock.turn_budget();
+        let outcomes: Vec<bool> = (0..4).map(|_| budget.is_exhausted()).collect();
+        assert_eq!(outcomes, vec![false, true, true, false]);
+        assert_eq!(clock.exhausted_polls(), vec![1, 2]);
+
+        clock.start(4);
+        assert!(!clock.turn_budget().is_exhausted());
+        assert!(clock.exhausted_polls().is_empty());
+    }
+
+    #[test]
+    fn live_clock_keeps_the_polls_that_ran_out() {
+        let mut clock = TurnClock::with_deadline(Duration::from_secs(0));
+        clock.start(1);
+        assert!(clock.turn_budget().is_exhausted());
+        assert!(clock.budget(1.0).is_exhausted());
+        assert_eq!(clock.exhausted_polls(), vec![0, 1]);
+
+        let mut clock = TurnClock::with_deadline(Duration::from_secs(60));
+        clock.start(1);
+        assert!(!clock.turn_budget().is_exhausted());
+        assert!(clock.exhausted_polls().is_empty());
+    }
+}
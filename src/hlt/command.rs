@@ -1,18 +1,345 @@
+use hlt::constants::Constants;
 use hlt::direction::Direction;
+use hlt::game::Game;
 use hlt::ShipId;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::fmt;
 
-pub struct Command(pub String);
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Command {
+    Spawn,
+    Move(ShipId, Direction),
+    Construct(ShipId),
+}
 
 impl Command {
     pub fn spawn_ship() -> Command {
-        Command(format!("g"))
+        Command::Spawn
     }
 
     pub fn transform_ship_into_dropoff_site(ship_id: ShipId) -> Command {
-        Command(format!("c {}", ship_id.0))
+        Command::Construct(ship_id)
     }
 
     pub fn move_ship(ship_id: ShipId, direction: Direction) -> Command {
-        Command(format!("m {} {}", ship_id.0, direction.get_char_encoding()))
+        Command::Move(ship_id, direction)
+    }
+
+    pub fn ship_id(&self) -> Option<ShipId> {
+        match *self {
+            Command::Spawn => None,
+            Command::Move(ship_id, _) => Some(ship_id),
+            Command::Construct(ship_id) => Some(ship_id),
+        }
+    }
+
+    // Reads the commands of a turn from a line in the wire format. Returns None if the line isn't
+    // valid protocol.
+    pub fn parse_line(line: &str) -> Option<Vec<Command>> {
+        let mut commands = Vec::new();
+        let mut tokens = line.split_whitespace();
+
+        while let Some(token) = tokens.next() {
+            let command = match token {
+                "g" => Command::Spawn,
+                "c" => Command::Construct(ShipId(tokens.next()?.parse().ok()?)),
+                "m" => {
+                    let ship_id = ShipId(tokens.next()?.parse().ok()?);
+                    let mut direction = tokens.next()?.chars();
+                    match (direction.next(), direction.next()) {
+                        (Some(c), None) => Command::Move(ship_id, Direction::from_char_encoding(c)?),
+                        _ => return None,
+                    }
+                },
+                _ => return None,
+            };
+            commands.push(command);
+        }
+
+        Some(commands)
+    }
+}
+
+impl fmt::Display for Command {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Command::Spawn => write!(f, "g"),
+            Command::Move(ship_id, direction) => write!(f, "m {} {}", ship_id.0, direction.get_char_encoding()),
+            Command::Construct(ship_id) => write!(f, "c {}", ship_id.0),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum CommandError {
+    UnknownShip(ShipId),
+    ShipAlreadyCommanded(ShipId),
+    ShipOnStructure(ShipId),
+    AlreadySpawning,
+    InsufficientHalite { needed: usize, available: usize },
+}
+
+impl fmt::Display for CommandError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CommandError::UnknownShip(ship_id) => write!(f, "ship {} is not ours", ship_id.0),
+            CommandError::ShipAlreadyCommanded(ship_id) => write!(f, "ship {} already has a command this turn", ship_id.0),
+            CommandError::ShipOnStructure(ship_id) => write!(f, "ship {} is on a shipyard or dropoff", ship_id.0),
+            CommandError::AlreadySpawning => write!(f, "a ship is already being spawned this turn"),
+            CommandError::InsufficientHalite { needed, available } => write!(f, "needs {} halite but only {} is available", needed, available),
+        }
+    }
+}
+
+struct ShipFunds {
+    cargo: usize,
+    cell_halite: usize,
+    inspired: bool,
+    on_structure: bool,
+}
+
+// Collects our commands for one turn and rejects the ones the engine would refuse or ignore:
+// a second command for the same ship, a second spawn, commanding a ship we don't own, building a
+// dropoff on top of a structure and spending halite we don't have. Spawns and dropoffs are paid from the same bank, so whatever is
+// accepted first reserves its cost. The state of our ships is copied when the buffer is created,
+// which leaves `game` free to be borrowed mutably while the turn is planned.
+pub struct CommandBuffer {
+    commands: Vec<Command>,
+    halite: usize,
+    constants: Constants,
+    ships: HashMap<ShipId, ShipFunds>,
+    commanded: HashSet<ShipId>,
+    spawning: bool,
+}
+
+impl CommandBuffer {
+    pub fn new(game: &Game) -> CommandBuffer {
+        let me = &game.players[game.my_id.0];
+
+        let mut ships = HashMap::new();
+        for ship_id in &me.ship_ids {
+            let ship = &game.ships[ship_id];
+            let cell = game.game_map.at_entity(ship);
+            ships.insert(*ship_id, ShipFunds {
+                cargo: ship.halite,
+                cell_halite: cell.halite,
+                inspired: game.game_map.is_inspired(&ship.position, ship.owner),
+                on_structure: cell.has_structure(),
+            });
+        }
+
+        CommandBuffer {
+            commands: Vec::new(),
+            halite: me.halite,
+            constants: game.constants.clone(),
+            ships,
+            commanded: HashSet::new(),
+            spawning: false,
+        }
+    }
+
+    pub fn push(&mut self, command: Command) -> Result<(), CommandError> {
+        match command {
+            Command::Spawn => {
+                if self.spawning {
+                    return Err(CommandError::AlreadySpawning);
+                }
+                self.spend(self.constants.ship_cost)?;
+                self.spawning = true;
+            },
+            Command::Move(ship_id, direction) => {
+                let move_cost = {
+                    let funds = self.unused_ship(ship_id)?;
+                    if direction == Direction::Still { 0 } else { self.constants.move_cost(funds.cell_halite, funds.inspired) }
+                };
+                let cargo = self.ships[&ship_id].cargo;
+                if cargo < move_cost {
+                    return Err(CommandError::InsufficientHalite { needed: move_cost, available: cargo });
+                }
+                self.commanded.insert(ship_id);
+            },
+            Command::Construct(ship_id) => {
+                let cost = {
+                    let funds = self.unused_ship(ship_id)?;
+                    if funds.on_structure {
+                        return Err(CommandError::ShipOnStructure(ship_id));
+                    }
+                    self.constants.construction_cost(funds.cargo, funds.cell_halite)
+                };
+                self.spend(cost)?;
+                self.commanded.insert(ship_id);
+            },
+        }
+
+        self.commands.push(command);
+        Ok(())
+    }
+
+    pub fn commands(&self) -> &[Command] {
+        &self.commands
+    }
+
+    pub fn is_commanded(&self, ship_id: ShipId) -> bool {
+        self.commanded.contains(&ship_id)
+    }
+
+    pub fn is_spawning(&self) -> bool {
+        self.spawning
+    }
+
+    // Bank halite not yet reserved by an accepted spawn or dropoff.
+    pub fn available_halite(&self) -> usize {
+        self.halite
+    }
+
+    fn unused_ship(&self, ship_id: ShipId) -> Result<&ShipFunds, CommandError> {
+        if self.commanded.contains(&ship_id) {
+            return Err(CommandError::ShipAlreadyCommanded(ship_id));
+        }
+        self.ships.get(&ship_id).ok_or(CommandError::UnknownShip(ship_id))
+    }
+
+    fn spend(&mut self, cost: usize) -> Result<(), CommandError> {
+        if self.halite < cost {
+            return Err(CommandError::InsufficientHalite { needed: cost, available: self.halite });
+        }
+        self.halite -= cost;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hlt::game_map::GameMap;
+    use hlt::player::Player;
+    use hlt::PlayerId;
+    use hlt::position::Position;
+    use hlt::simulator::Simulator;
+
+    // Two players on a 32x32 map with the same halite on every cell, with player 0 starting out
+    // with `bank` halite.
+    fn simulator(cell_halite: usize, bank: usize) -> Simulator {
+        let constants = Constants::default_for_map(32);
+        let game_map = GameMap::new(32, 32, &vec![vec![cell_halite; 32]; 32]);
+        let players = vec![
+            Player::new(PlayerId(0), Position { x: 8, y: 16 }),
+            Player::new(PlayerId(1), Position { x: 24, y: 16 }),
+        ];
+        let mut simulator = Simulator::new(constants, game_map, players);
+        simulator.players[0].halite = bank;
+        simulator
+    }
+
+    #[test]
+    fn moving_costs_a_tenth_of_the_cell() {
+        let mut simulator = simulator(155, 0);
+        let short = simulator.add_ship(PlayerId(0), Position { x: 3, y: 3 }, 14);
+        let enough = simulator.add_ship(PlayerId(0), Position { x: 3, y: 5 }, 15);
+        let game = Game::from_simulator(&simulator, PlayerId(0));
+
+        let mut buffer = CommandBuffer::new(&game);
+        assert_eq!(buffer.push(Command::Move(short, Direction::East)), Err(CommandError::InsufficientHalite { needed: 15, available: 14 }));
+        assert_eq!(buffer.push(Command::Move(short, Direction::Still)), Ok(()));
+        assert_eq!(buffer.push(Command::Move(enough, Direction::East)), Ok(()));
+    }
+
+    #[test]
+    fn inspired_ships_move_at_the_inspired_rate() {
+        let mut simulator = simulator(155, 0);
+        simulator.constants.inspired_move_cost_ratio = 20;
+        let ship_id = simulator.add_ship(PlayerId(0), Position { x: 5, y: 5 }, 7);
+        simulator.add_ship(PlayerId(1), Position { x: 6, y: 5 }, 0);
+        simulator.add_ship(PlayerId(1), Position { x: 5, y: 7 }, 0);
+        let game = Game::from_simulator(&simulator, PlayerId(0));
+        assert!(game.game_map.is_inspired(&Position { x: 5, y: 5 }, PlayerId(0)));
+
+        let mut buffer = CommandBuffer::new(&game);
+        assert_eq!(buffer.push(Command::Move(ship_id, Direction::North)), Ok(()));
+    }
+
+    #[test]
+    fn rejects_ships_that_are_not_ours_or_already_commanded() {
+        let mut simulator = simulator(0, 0);
+        let ours = simulator.add_ship(PlayerId(0), Position { x: 3, y: 3 }, 0);
+        let theirs = simulator.add_ship(PlayerId(1), Position { x: 20, y: 3 }, 0);
+        let game = Game::from_simulator(&simulator, PlayerId(0));
+
+        let mut buffer = CommandBuffer::new(&game);
+        assert_eq!(buffer.push(Command::Move(theirs, Direction::North)), Err(CommandError::UnknownShip(theirs)));
+        assert_eq!(buffer.push(Command::Move(ours, Direction::North)), Ok(()));
+        assert_eq!(buffer.push(Command::Construct(ours)), Err(CommandError::ShipAlreadyCommanded(ours)));
+        assert_eq!(buffer.commands(), &[Command::Move(ours, Direction::North)]);
+    }
+
+    #[test]
+    fn spawns_and_dropoffs_share_the_bank() {
+        let mut simulator = simulator(100, 4500);
+        let builder = simulator.add_ship(PlayerId(0), Position { x: 3, y: 3 }, 400);
+        let game = Game::from_simulator(&simulator, PlayerId(0));
+
+        let mut buffer = CommandBuffer::new(&game);
+        assert_eq!(buffer.push(Command::Spawn), Ok(()));
+        assert_eq!(buffer.push(Command::Spawn), Err(CommandError::AlreadySpawning));
+        assert_eq!(buffer.available_halite(), 3500);
+        assert_eq!(buffer.push(Command::Construct(builder)), Ok(()));
+        assert_eq!(buffer.available_halite(), 0);
+    }
+
+    #[test]
+    fn rejects_dropoffs_it_cannot_pay_for() {
+        let mut simulator = simulator(100, 3499);
+        let builder = simulator.add_ship(PlayerId(0), Position { x: 3, y: 3 }, 400);
+        let game = Game::from_simulator(&simulator, PlayerId(0));
+
+        let mut buffer = CommandBuffer::new(&game);
+        assert_eq!(buffer.push(Command::Construct(builder)), Err(CommandError::InsufficientHalite { needed: 3500, available: 3499 }));
+        assert!(!buffer.is_commanded(builder));
+    }
+
+    #[test]
+    fn rejects_dropoffs_on_a_shipyard_or_dropoff() {
+        let mut simulator = simulator(0, 10000);
+        simulator.add_dropoff(PlayerId(0), Position { x: 3, y: 3 });
+        let on_shipyard = simulator.add_ship(PlayerId(0), Position { x: 8, y: 16 }, 0);
+        let on_dropoff = simulator.add_ship(PlayerId(0), Position { x: 3, y: 3 }, 0);
+        let game = Game::from_simulator(&simulator, PlayerId(0));
+
+        let mut buffer = CommandBuffer::new(&game);
+        assert_eq!(buffer.push(Command::Construct(on_shipyard)), Err(CommandError::ShipOnStructure(on_shipyard)));
+        assert_eq!(buffer.push(Command::Construct(on_dropoff)), Err(CommandError::ShipOnStructure(on_dropoff)));
+        assert_eq!(buffer.available_halite(), 10000);
+    }
+
+    #[test]
+    fn parses_every_command_type() {
+        let commands = Command::parse_line("g m 3 n c 7 m 12 o");
+        assert_eq!(commands, Some(vec![
+            Command::Spawn,
+            Command::Move(ShipId(3), Direction::North),
+            Command::Construct(ShipId(7)),
+            Command::Move(ShipId(12), Direction::Still),
+        ]));
+    }
+
+    #[test]
+    fn parses_what_display_writes() {
+        let commands = vec![Command::Move(ShipId(0), Direction::West), Command::Construct(ShipId(4)), Command::Spawn];
+        let line: Vec<String> = commands.iter().map(|command| command.to_string()).collect();
+        assert_eq!(Command::parse_line(&line.join(" ")), Some(commands));
+    }
+
+    #[test]
+    fn empty_line_has_no_commands() {
+        assert_eq!(Command::parse_line(""), Some(Vec::new()));
+        assert_eq!(Command::parse_line("  \t"), Some(Vec::new()));
+    }
+
+    #[test]
+    fn rejects_lines_that_are_not_protocol() {
+        for line in &["x", "m", "m 3", "m 3 q", "m 3 nn", "m -1 n", "m a n", "c", "c b", "g 1"] {
+            assert_eq!(Command::parse_line(line), None, "{:?} should not parse", line);
+        }
     }
 }
@@ -0,0 +1,375 @@
+use hlt::game_map::RiskPolicy;
+use serde_json;
+use serde_json::Map;
+use serde_json::Value;
+use std::fs;
+
+// Every tunable number the bot plays by. Defaults depend on the map size; a JSON file and command
+// line overrides are applied on top of them with `load`.
+//
+// The file holds settings by name at the top level, plus an optional list of profiles that only
+// apply to some map sizes or player counts, each with its own settings:
+//
+//     {
+//         "return_margin": 200,
+//         "profiles": [
+//             { "map_sizes": [32, 40], "players": [4], "settings": { "max_dropoffs": 0 } }
+//         ]
+//     }
+//
+// Profiles leaving out `map_sizes` or `players` match any. Matching profiles apply in order.
+#[derive(Clone, Debug)]
+pub struct StrategyConfig {
+    // Exploring ships head home once they carry at least `max_halite - return_margin`.
+    pub return_margin: usize,
+    // Ships without a mining target wander towards the closest cell holding at least this much.
+    pub rich_halite: usize,
+
+    pub max_search_distance: usize,
+    pub max_mining_turns: usize,
+    pub candidates_per_ship: usize,
+    pub auction_fleet_size: usize,
+    pub auction_epsilon: f64,
+    // Share of the time left in the turn that assigning mining targets may use.
+    pub assignment_time_share: f64,
+
+    pub first_dropoff_turn: usize,
+    pub ships_per_dropoff: usize,
+    pub max_dropoffs: usize,
+    pub dropoff_min_turns_left: usize,
+    pub dropoff_candidate_sites: usize,
+    // Dropoff sites are rated by the halite within this radius and kept this many radii apart.
+    pub dropoff_zone_radius: usize,
+    pub dropoff_spacing: usize,
+    // How much each enemy ship near a site takes off its rating: n ships divide it by 1 + n times this.
+    pub dropoff_crowding: f64,
+    // Ships within this many times the closest ship's distance to a site, plus a turn, may be sent
+    // to build there; the one with the most cargo goes.
+    pub dropoff_builder_reach: f64,
+    // A build that hasn't happened after this many turns is given up, as is one the bank can't
+    // pay for once too little of the game is left.
+    pub dropoff_max_build_turns: usize,
+
+    pub spawn_mining_efficiency: f64,
+    pub spawn_min_cell_halite: usize,
+    pub spawn_required_return: f64,
+    // Turns a new ship spends reaching the halite, as a share of the map's width.
+    pub spawn_startup_share: f64,
+
+    pub return_arrivals_per_turn: usize,
+    pub return_safety_margin: usize,
+
+    pub risk_policy: RiskPolicy,
+
+    // Move priorities handed to the resolver for ships in each state.
+    pub miner_priority: usize,
+    pub returning_priority: usize,
+    pub builder_priority: usize,
+    pub rush_priority: usize,
+}
+
+impl StrategyConfig {
+    pub fn for_map(map_width: usize) -> StrategyConfig {
+        let (max_dropoffs, dropoff_zone_radius) = match map_width {
+            0..=32 => (1, 3),
+            33..=48 => (2, 4),
+            49..=56 => (3, 5),
+            // The engine makes maps at most 64 wide.
+            _ => (4, 5),
+        };
+
+        StrategyConfig {
+            return_margin: 250,
+            rich_halite: 25,
+            max_search_distance: 12,
+            max_mining_turns: 12,
+            candidates_per_ship: 10,
+            auction_fleet_size: 80,
+            auction_epsilon: 0.01,
+            assignment_time_share: 0.5,
+            first_dropoff_turn: 40,
+            ships_per_dropoff: 6,
+            max_dropoffs,
+            dropoff_min_turns_left: 100,
+            dropoff_candidate_sites: 3,
+            dropoff_zone_radius,
+            dropoff_spacing: 3,
+            dropoff_crowding: 0.2,
+            dropoff_builder_reach: 1.5,
+            dropoff_max_build_turns: 50,
+            spawn_mining_efficiency: 0.35,
+            spawn_min_cell_halite: 30,
+            spawn_required_return: 1.0,
+            spawn_startup_share: 0.25,
+            return_arrivals_per_turn: 4,
+            return_safety_margin: 3,
+            risk_policy: RiskPolicy::AcceptIfCarryingLess,
+            miner_priority: 1,
+            returning_priority: 2,
+            builder_priority: 2,
+            rush_priority: 3,
+        }
+    }
+
+    // The defaults for the game, then the settings and matching profiles of the file if there is
+    // one, then the overrides given as `name=value`.
+    pub fn load(map_width: usize, num_players: usize, path: Option<&str>, overrides: &[String]) -> Result<StrategyConfig, String> {
+        let mut config = StrategyConfig::for_map(map_width);
+
+        if let Some(path) = path {
+            let contents = fs::read_to_string(path)
+                .map_err(|e| format!("Error: config: could not read {}: {}", path, e))?;
+            let root: Value = serde_json::from_str(&contents)
+                .map_err(|e| format!("Error: config: {} is not valid JSON: {}", path, e))?;
+            config.apply_file(&root, map_width, num_players)?;
+        }
+
+        config.apply_overrides(overrides)?;
+        Ok(config)
+    }
+
+    // Applies settings given as `name=value`, such as those from the command line.
+    pub fn apply_overrides(&mut self, overrides: &[String]) -> Result<(), String> {
+        for assignment in overrides {
+            let (name, value) = match assignment.find('=') {
+                Some(index) => (&assignment[..index], &assignment[index + 1..]),
+                None => return Err(format!("Error: config: expected name=value, got '{}'.", assignment)),
+            };
+            // Plain words like `avoid` aren't JSON, so take them as strings.
+            let value = serde_json::from_str(value).unwrap_or_else(|_| Value::String(value.to_string()));
+            self.set(name.trim(), &value)?;
+        }
+        Ok(())
+    }
+
+    pub fn apply_file(&mut self, root: &Value, map_width: usize, num_players: usize) -> Result<(), String> {
+        let settings = root.as_object().ok_or("Error: config: expected an object at the top level.")?;
+        for (name, value) in settings.iter().filter(|&(name, _)| name != "profiles") {
+            self.set(name, value)?;
+        }
+
+        let profiles = match root.get("profiles") {
+            Some(profiles) => profiles.as_array().ok_or("Error: config: profiles should be a list.")?.clone(),
+            None => Vec::new(),
+        };
+        for profile in &profiles {
+            if StrategyConfig::profile_matches(profile, "map_sizes", map_width)? &&
+                StrategyConfig::profile_matches(profile, "players", num_players)?
+            {
+                let settings = profile.get("settings").and_then(|settings| settings.as_object())
+                    .ok_or("Error: config: every profile needs an object of settings.")?;
+                for (name, value) in settings {
+                    self.set(name, value)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn set(&mut self, name: &str, value: &Value) -> Result<(), String> {
+        let count = || StrategyConfig::count(name, value);
+        let fraction = || StrategyConfig::fraction(name, value);
+
+        match name {
+            "return_margin" => self.return_margin = count()?,
+            "rich_halite" => self.rich_halite = count()?,
+            "max_search_distance" => self.max_search_distance = count()?,
+            "max_mining_turns" => self.max_mining_turns = count()?,
+            "candidates_per_ship" => self.candidates_per_ship = count()?,
+            "auction_fleet_size" => self.auction_fleet_size = count()?,
+            "auction_epsilon" => {
+                // The auction only ends because every bid raises a price by at least epsilon.
+                let epsilon = fraction()?;
+                if epsilon <= 0.0 {
+                    return Err(format!("Error: config: auction_epsilon should be greater than zero, got {}.", value));
+                }
+                self.auction_epsilon = epsilon;
+            },
+            "assignment_time_share" => self.assignment_time_share = fraction()?,
+            "first_dropoff_turn" => self.first_dropoff_turn = count()?,
+            "ships_per_dropoff" => self.ships_per_dropoff = count()?,
+            "max_dropoffs" => self.max_dropoffs = count()?,
+            "dropoff_min_turns_left" => self.dropoff_min_turns_left = count()?,
+            "dropoff_candidate_sites" => self.dropoff_candidate_sites = count()?,
+            "dropoff_zone_radius" => self.dropoff_zone_radius = count()?,
+            "dropoff_spacing" => self.dropoff_spacing = count()?,
+            "dropoff_crowding" => self.dropoff_crowding = fraction()?,
+            "dropoff_builder_reach" => self.dropoff_builder_reach = fraction()?,
+            "dropoff_max_build_turns" => self.dropoff_max_build_turns = count()?,
+            "spawn_mining_efficiency" => self.spawn_mining_efficiency = fraction()?,
+            "spawn_min_cell_halite" => self.spawn_min_cell_halite = count()?,
+            "spawn_required_return" => self.spawn_required_return = fraction()?,
+            "spawn_startup_share" => self.spawn_startup_share = fraction()?,
+            "return_arrivals_per_turn" => self.return_arrivals_per_turn = count()?,
+            "return_safety_margin" => self.return_safety_margin = count()?,
+            "risk_policy" => {
+                self.risk_policy = match value.as_str() {
+                    Some("avoid") => RiskPolicy::Avoid,
+                    Some("accept_if_carrying_less") => RiskPolicy::AcceptIfCarryingLess,
+                    Some("collide") => RiskPolicy::Collide,
+                    _ => return Err(format!("Error: config: risk_policy should be avoid, accept_if_carrying_less or collide, got {}.", value)),
+                }
+            },
+            "miner_priority" => self.miner_priority = count()?,
+            "returning_priority" => self.returning_priority = count()?,
+            "builder_priority" => self.builder_priority = count()?,
+            "rush_priority" => self.rush_priority = count()?,
+            _ => return Err(format!("Error: config: unknown setting {}.", name)),
+        }
+        Ok(())
+    }
+
+    // All settings by name, in the format `apply_file` reads.
+    pub fn to_json(&self) -> Value {
+        let mut settings = Map::new();
+        let mut count = |name: &str, value: usize| { settings.insert(name.to_string(), Value::from(value)); };
+        count("return_margin", self.return_margin);
+        count("rich_halite", self.rich_halite);
+        count("max_search_distance", self.max_search_distance);
+        count("max_mining_turns", self.max_mining_turns);
+        count("candidates_per_ship", self.candidates_per_ship);
+        count("auction_fleet_size", self.auction_fleet_size);
+        count("first_dropoff_turn", self.first_dropoff_turn);
+        count("ships_per_dropoff", self.ships_per_dropoff);
+        count("max_dropoffs", self.max_dropoffs);
+        count("dropoff_min_turns_left", self.dropoff_min_turns_left);
+        count("dropoff_candidate_sites", self.dropoff_candidate_sites);
+        count("dropoff_zone_radius", self.dropoff_zone_radius);
+        count("dropoff_spacing", self.dropoff_spacing);
+        count("dropoff_max_build_turns", self.dropoff_max_build_turns);
+        count("spawn_min_cell_halite", self.spawn_min_cell_halite);
+        count("return_arrivals_per_turn", self.return_arrivals_per_turn);
+        count("return_safety_margin", self.return_safety_margin);
+        count("miner_priority", self.miner_priority);
+        count("returning_priority", self.returning_priority);
+        count("builder_priority", self.builder_priority);
+        count("rush_priority", self.rush_priority);
+
+        let mut fraction = |name: &str, value: f64| { settings.insert(name.to_string(), Value::from(value)); };
+        fraction("auction_epsilon", self.auction_epsilon);
+        fraction("assignment_time_share", self.assignment_time_share);
+        fraction("dropoff_crowding", self.dropoff_crowding);
+        fraction("dropoff_builder_reach", self.dropoff_builder_reach);
+        fraction("spawn_mining_efficiency", self.spawn_mining_efficiency);
+        fraction("spawn_required_return", self.spawn_required_return);
+        fraction("spawn_startup_share", self.spawn_startup_share);
+
+        let risk_policy = match self.risk_policy {
+            RiskPolicy::Avoid => "avoid",
+            RiskPolicy::AcceptIfCarryingLess => "accept_if_carrying_less",
+            RiskPolicy::Collide => "collide",
+        };
+        settings.insert("risk_policy".to_string(), Value::from(risk_policy));
+
+        Value::Object(settings)
+    }
+
+    fn profile_matches(profile: &Value, key: &str, actual: usize) -> Result<bool, String> {
+        match profile.get(key) {
+            None => Ok(true),
+            Some(Value::Array(values)) => Ok(values.iter().any(|value| value.as_u64() == Some(actual as u64))),
+            Some(_) => Err(format!("Error: config: profile {} should be a list of numbers.", key)),
+        }
+    }
+
+    fn count(name: &str, value: &Value) -> Result<usize, String> {
+        value.as_u64().map(|value| value as usize)
+            .ok_or_else(|| format!("Error: config: {} should be a whole number, got {}.", name, value))
+    }
+
+    fn fraction(name: &str, value: &Value) -> Result<f64, String> {
+        value.as_f64().ok_or_else(|| format!("Error: config: {} should be a number, got {}.", name, value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::env;
+    use std::process;
+
+    #[test]
+    fn auction_epsilon_must_be_positive_wherever_it_is_set() {
+        let mut config = StrategyConfig::for_map(32);
+        assert!(config.apply_overrides(&["auction_epsilon=0".to_string()]).is_err());
+        assert!(config.apply_file(&json!({ "auction_epsilon": -0.5 }), 32, 2).is_err());
+
+        let profile = json!({ "profiles": [{ "map_sizes": [32], "settings": { "auction_epsilon": 0.0 } }] });
+        assert!(config.apply_file(&profile, 32, 2).is_err());
+        assert!(config.auction_epsilon > 0.0);
+    }
+
+    #[test]
+    fn defaults_grow_with_the_map_up_to_64_wide() {
+        let dropoffs = |width: usize| {
+            let config = StrategyConfig::for_map(width);
+            (config.max_dropoffs, config.dropoff_zone_radius)
+        };
+        assert_eq!(dropoffs(32), (1, 3));
+        assert_eq!(dropoffs(40), (2, 4));
+        assert_eq!(dropoffs(48), (2, 4));
+        assert_eq!(dropoffs(56), (3, 5));
+        assert_eq!(dropoffs(64), (4, 5));
+        assert_eq!(StrategyConfig::for_map(64).dropoff_crowding, 0.2);
+    }
+
+    #[test]
+    fn profiles_apply_by_map_size_and_player_count() {
+        let root = json!({
+            "dropoff_crowding": 0.5,
+            "profiles": [
+                { "map_sizes": [40, 64], "settings": { "max_dropoffs": 7 } },
+                { "players": [4], "settings": { "dropoff_crowding": 0.9 } },
+                { "map_sizes": [64], "players": [2], "settings": { "max_dropoffs": 9 } }
+            ]
+        });
+        let loaded = |width: usize, players: usize| {
+            let mut config = StrategyConfig::for_map(width);
+            config.apply_file(&root, width, players).expect("the file should apply");
+            (config.max_dropoffs, config.dropoff_crowding)
+        };
+
+        assert_eq!(loaded(32, 2), (1, 0.5));
+        assert_eq!(loaded(40, 2), (7, 0.5));
+        assert_eq!(loaded(40, 4), (7, 0.9));
+        assert_eq!(loaded(64, 4), (7, 0.9));
+        // Later matching profiles override earlier ones.
+        assert_eq!(loaded(64, 2), (9, 0.5));
+    }
+
+    #[test]
+    fn overrides_win_over_the_file_and_its_profiles() {
+        let path = env::temp_dir().join(format!("config-test-{}.json", process::id()));
+        let root = json!({
+            "return_margin": 100,
+            "rich_halite": 40,
+            "profiles": [{ "map_sizes": [48], "settings": { "return_margin": 150, "risk_policy": "avoid" } }]
+        });
+        fs::write(&path, root.to_string()).unwrap();
+        let path_name = path.to_str().unwrap();
+
+        let config = StrategyConfig::load(48, 2, Some(path_name), &["return_margin=175".to_string()]).unwrap();
+        assert_eq!(config.return_margin, 175);
+        assert_eq!(config.rich_halite, 40);
+        assert_eq!(config.risk_policy, RiskPolicy::Avoid);
+
+        let config = StrategyConfig::load(32, 2, Some(path_name), &[]).unwrap();
+        assert_eq!(config.return_margin, 100);
+        assert_eq!(config.risk_policy, RiskPolicy::AcceptIfCarryingLess);
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn bad_files_and_settings_are_rejected() {
+        let mut config = StrategyConfig::for_map(32);
+        assert!(config.apply_file(&json!([1, 2]), 32, 2).is_err());
+        assert!(config.apply_file(&json!({ "no_such_setting": 1 }), 32, 2).is_err());
+        assert!(config.apply_file(&json!({ "max_dropoffs": -1 }), 32, 2).is_err());
+        assert!(config.apply_file(&json!({ "profiles": [{ "map_sizes": 32, "settings": {} }] }), 32, 2).is_err());
+        assert!(config.apply_file(&json!({ "profiles": [{ "map_sizes": [32] }] }), 32, 2).is_err());
+        assert!(config.apply_overrides(&["max_dropoffs".to_string()]).is_err());
+        assert!(StrategyConfig::load(32, 2, Some("/nonexistent/config.json"), &[]).is_err());
+    }
+}
@@ -1,12 +1,15 @@
 use hlt::log::Log;
+use std::cmp::min;
 use std::collections::HashMap;
 use std::str::FromStr;
 
+#[derive(Clone)]
 pub struct Constants {
     pub max_halite: usize,
     pub ship_cost: usize,
     pub dropoff_cost: usize,
     pub max_turns: usize,
+    pub initial_halite: usize,
     pub extract_ratio: usize,
     pub move_cost_ratio: usize,
     pub inspiration_enabled: bool,
@@ -19,12 +22,19 @@ pub struct Constants {
 
 impl Constants {
     pub fn new(log: &mut Log, string_from_engine: &str) -> Constants {
+        match Constants::parse(string_from_engine) {
+            Ok(constants) => constants,
+            Err(message) => log.panic(&message),
+        }
+    }
+
+    pub fn parse(string_from_engine: &str) -> Result<Constants, String> {
         let token_iter = string_from_engine.split(|c| " {},:\"\r\n".contains(c));
         let token_iter = token_iter.filter(|x| !x.is_empty());
         let tokens: Vec<&str> = token_iter.collect();
 
-        if (tokens.len() % 2) != 0 {
-            log.panic("Error: constants: expected even total number of key and value tokens from server.");
+        if !tokens.len().is_multiple_of(2) {
+            return Err("Error: constants: expected even total number of key and value tokens from server.".to_string());
         }
 
         let mut map = HashMap::new();
@@ -33,34 +43,104 @@ impl Constants {
             map.insert(tokens[i].to_string(), tokens[i+1].to_string());
         }
 
+        Ok(Constants {
+            ship_cost: Constants::get_value(&map, "NEW_ENTITY_ENERGY_COST")?,
+            dropoff_cost: Constants::get_value(&map, "DROPOFF_COST")?,
+            max_halite: Constants::get_value(&map, "MAX_ENERGY")?,
+            max_turns: Constants::get_value(&map, "MAX_TURNS")?,
+            initial_halite: Constants::get_value(&map, "INITIAL_ENERGY")?,
+            extract_ratio: Constants::get_value(&map, "EXTRACT_RATIO")?,
+            move_cost_ratio: Constants::get_value(&map, "MOVE_COST_RATIO")?,
+            inspiration_enabled: Constants::get_value(&map, "INSPIRATION_ENABLED")?,
+            inspiration_radius: Constants::get_value(&map, "INSPIRATION_RADIUS")?,
+            inspiration_ship_count: Constants::get_value(&map, "INSPIRATION_SHIP_COUNT")?,
+            inspired_extract_ratio: Constants::get_value(&map, "INSPIRED_EXTRACT_RATIO")?,
+            inspired_bonus_multiplier: Constants::get_value(&map, "INSPIRED_BONUS_MULTIPLIER")?,
+            inspired_move_cost_ratio: Constants::get_value(&map, "INSPIRED_MOVE_COST_RATIO")?,
+        })
+    }
+
+    // The values the official engine uses when no overrides are given. The number of turns scales
+    // linearly with the map width from 400 turns on 32x32 maps to 500 turns on 64x64 maps.
+    pub fn default_for_map(map_width: usize) -> Constants {
+        let clamped_width = map_width.clamp(32, 64);
+
         Constants {
-            ship_cost: Constants::get_value(log, &map, "NEW_ENTITY_ENERGY_COST"),
-            dropoff_cost: Constants::get_value(log, &map, "DROPOFF_COST"),
-            max_halite: Constants::get_value(log, &map, "MAX_ENERGY"),
-            max_turns: Constants::get_value(log, &map, "MAX_TURNS"),
-            extract_ratio: Constants::get_value(log, &map, "EXTRACT_RATIO"),
-            move_cost_ratio: Constants::get_value(log, &map, "MOVE_COST_RATIO"),
-            inspiration_enabled: Constants::get_value(log, &map, "INSPIRATION_ENABLED"),
-            inspiration_radius: Constants::get_value(log, &map, "INSPIRATION_RADIUS"),
-            inspiration_ship_count: Constants::get_value(log, &map, "INSPIRATION_SHIP_COUNT"),
-            inspired_extract_ratio: Constants::get_value(log, &map, "INSPIRED_EXTRACT_RATIO"),
-            inspired_bonus_multiplier: Constants::get_value(log, &map, "INSPIRED_BONUS_MULTIPLIER"),
-            inspired_move_cost_ratio: Constants::get_value(log, &map, "INSPIRED_MOVE_COST_RATIO"),
+            ship_cost: 1000,
+            dropoff_cost: 4000,
+            max_halite: 1000,
+            max_turns: 400 + (clamped_width - 32) * 100 / 32,
+            initial_halite: 5000,
+            extract_ratio: 4,
+            move_cost_ratio: 10,
+            inspiration_enabled: true,
+            inspiration_radius: 4,
+            inspiration_ship_count: 2,
+            inspired_extract_ratio: 4,
+            inspired_bonus_multiplier: 2.0,
+            inspired_move_cost_ratio: 10,
         }
     }
 
-    fn get_value<T: FromStr>(log: &mut Log, map: &HashMap<String, String>, key: &str) -> T {
-        let s = Constants::get_string(log, map, key);
+    // Serializes the constants the way the engine sends them to bots and stores them in replays,
+    // including the constants the engine uses internally that bots don't parse.
+    pub fn to_engine_string(&self, game_seed: u64) -> String {
+        format!(
+            concat!(
+                "{{\"CAPTURE_ENABLED\":false,\"CAPTURE_RADIUS\":3,\"DEFAULT_MAP_HEIGHT\":48,\"DEFAULT_MAP_WIDTH\":48,",
+                "\"DROPOFF_COST\":{},\"DROPOFF_PENALTY_RATIO\":4,\"EXTRACT_RATIO\":{},\"FACTOR_EXP_1\":2.0,",
+                "\"FACTOR_EXP_2\":2.0,\"INITIAL_ENERGY\":{},\"INSPIRATION_ENABLED\":{},\"INSPIRATION_RADIUS\":{},",
+                "\"INSPIRATION_SHIP_COUNT\":{},\"INSPIRED_BONUS_MULTIPLIER\":{:?},\"INSPIRED_EXTRACT_RATIO\":{},",
+                "\"INSPIRED_MOVE_COST_RATIO\":{},\"MAX_CELL_PRODUCTION\":1000,\"MAX_ENERGY\":{},\"MAX_PLAYERS\":16,",
+                "\"MAX_TURNS\":{},\"MAX_TURN_THRESHOLD\":64,\"MIN_CELL_PRODUCTION\":900,\"MIN_TURNS\":400,",
+                "\"MIN_TURN_THRESHOLD\":32,\"MOVE_COST_RATIO\":{},\"NEW_ENTITY_ENERGY_COST\":{},\"PERSISTENCE\":0.7,",
+                "\"SHIPS_ABOVE_FOR_CAPTURE\":3,\"STRICT_ERRORS\":false,\"game_seed\":{}}}"
+            ),
+            self.dropoff_cost, self.extract_ratio, self.initial_halite, self.inspiration_enabled,
+            self.inspiration_radius, self.inspiration_ship_count, self.inspired_bonus_multiplier,
+            self.inspired_extract_ratio, self.inspired_move_cost_ratio, self.max_halite, self.max_turns,
+            self.move_cost_ratio, self.ship_cost, game_seed)
+    }
+
+    pub fn move_cost(&self, cell_halite: usize, inspired: bool) -> usize {
+        let ratio = if inspired { self.inspired_move_cost_ratio } else { self.move_cost_ratio };
+        cell_halite / ratio
+    }
+
+    // Returns the halite taken from the cell and the halite gained by the ship, which differ when the
+    // ship is inspired and receives the bonus on top of what it extracted.
+    pub fn extraction(&self, cell_halite: usize, ship_halite: usize, inspired: bool) -> (usize, usize) {
+        let ratio = if inspired { self.inspired_extract_ratio } else { self.extract_ratio };
+        let room = self.max_halite.saturating_sub(ship_halite);
+        let extracted = min(cell_halite.div_ceil(ratio), room);
+
+        let bonus = if inspired {
+            min((extracted as f64 * self.inspired_bonus_multiplier) as usize, room - extracted)
+        } else {
+            0
+        };
+
+        (extracted, extracted + bonus)
+    }
+
+    // Halite the player has to pay from its bank to turn a ship into a dropoff. The ship's cargo and
+    // the halite on the cell are credited towards the cost.
+    pub fn construction_cost(&self, ship_halite: usize, cell_halite: usize) -> usize {
+        self.dropoff_cost.saturating_sub(ship_halite + cell_halite)
+    }
+
+    fn get_value<T: FromStr>(map: &HashMap<String, String>, key: &str) -> Result<T, String> {
+        let s = Constants::get_string(map, key)?;
         match s.parse::<T>() {
-            Ok(x) => x,
-            Err(_) => log.panic(&format!("Error: constants: for {} got '{}' from server and failed to parse that.", key, s))
+            Ok(x) => Ok(x),
+            Err(_) => Err(format!("Error: constants: for {} got '{}' from server and failed to parse that.", key, s))
         }
     }
 
-    fn get_string<'a>(log: &mut Log, map: &'a HashMap<String, String>, key: &str) -> &'a String {
+    fn get_string<'a>(map: &'a HashMap<String, String>, key: &str) -> Result<&'a String, String> {
         match map.get(key) {
-            Some(x) => x,
-            None => log.panic(&format!("Error: constants: server did not send {} constant.", key))
+            Some(x) => Ok(x),
+            None => Err(format!("Error: constants: server did not send {} constant.", key))
         }
     }
 }
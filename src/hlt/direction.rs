@@ -22,6 +22,17 @@ impl Direction {
         vec![Direction::North, Direction::South, Direction::East, Direction::West]
     }
 
+    pub fn from_char_encoding(c: char) -> Option<Direction> {
+        match c {
+            'n' => Some(Direction::North),
+            'e' => Some(Direction::East),
+            's' => Some(Direction::South),
+            'w' => Some(Direction::West),
+            'o' => Some(Direction::Still),
+            _ => None,
+        }
+    }
+
     pub fn get_char_encoding(&self) -> char {
         match self {
             Direction::North => 'n',
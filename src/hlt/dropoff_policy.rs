@@ -0,0 +1,211 @@
+use hlt::clock::Budget;
+use hlt::config::StrategyConfig;
+use hlt::fleet::Fleet;
+use hlt::fleet::ShipState;
+use hlt::game::Game;
+use hlt::position::Position;
+use hlt::ShipId;
+
+pub struct DropoffPlan {
+    pub ship_id: ShipId,
+    pub site: Position,
+    // What the build will take from the bank once the ship's cargo and the site's halite are
+    // credited, judging by the ship's cargo now.
+    pub bank_cost: usize,
+}
+
+// Decides when we build dropoffs, which ship goes and how much of the bank to hold back for it.
+// Only one dropoff is planned at a time. A new one is planned when the fleet has grown enough to
+// feed another dropoff, we are below the number of dropoffs the map size warrants and enough of
+// the game is left for the dropoff to pay for itself. A build that is given up holds off the next
+// one for as long as a build may take, so the bank it was holding gets spent on ships meanwhile.
+pub struct DropoffPolicy {
+    pub first_build_turn: usize,
+    // Ships we want per dropoff, counting the shipyard as one.
+    pub ships_per_dropoff: usize,
+    pub max_dropoffs: usize,
+    pub min_turns_left: usize,
+    // Sites asked from the map each time a new dropoff is planned, and how they are rated.
+    pub candidate_sites: usize,
+    pub zone_radius: usize,
+    pub min_spacing: usize,
+    pub crowding: f64,
+    pub builder_reach: f64,
+    pub max_build_turns: usize,
+    // The first turn a new build may be planned after one was given up.
+    resume_turn: usize,
+}
+
+impl DropoffPolicy {
+    pub fn new(config: &StrategyConfig) -> DropoffPolicy {
+        DropoffPolicy {
+            first_build_turn: config.first_dropoff_turn,
+            ships_per_dropoff: config.ships_per_dropoff,
+            max_dropoffs: config.max_dropoffs,
+            min_turns_left: config.dropoff_min_turns_left,
+            candidate_sites: config.dropoff_candidate_sites,
+            zone_radius: config.dropoff_zone_radius,
+            min_spacing: config.dropoff_spacing * config.dropoff_zone_radius,
+            crowding: config.dropoff_crowding,
+            builder_reach: config.dropoff_builder_reach,
+            max_build_turns: config.dropoff_max_build_turns,
+            resume_turn: 0,
+        }
+    }
+
+    pub fn wants_dropoff(&self, game: &Game) -> bool {
+        let me = &game.players[game.my_id.0];
+        let structures = 1 + me.dropoff_ids.len();
+
+        game.turn_number >= self.first_build_turn &&
+            game.turns_left() >= self.min_turns_left &&
+            me.dropoff_ids.len() < self.max_dropoffs &&
+            me.ship_ids.len() >= self.ships_per_dropoff * structures
+    }
+
+    // Picks a site and a ship to send there, if we want another dropoff and none is underway.
+    // The ship is the one with the most cargo among those that can get there about as fast as the
+    // closest one, since the engine credits the cargo towards the cost. Nothing is planned when the
+    // budget runs out while looking for a site.
+    pub fn plan(&self, game: &Game, fleet: &Fleet, budget: &Budget) -> Option<DropoffPlan> {
+        if game.turn_number < self.resume_turn || !self.wants_dropoff(game) || !self.builders(fleet).is_empty() {
+            return None;
+        }
+
+        let me = &game.players[game.my_id.0];
+        let candidates: Vec<ShipId> = me.ship_ids.iter()
+            .filter(|ship_id| fleet.state(**ship_id) == ShipState::Exploring || fleet.state(**ship_id) == ShipState::Returning)
+            .cloned()
+            .collect();
+        let rng = &mut game.rng.borrow_mut();
+        let sites = game.game_map.find_suitable_dropoffs(game.my_id, &game.ships, self.candidate_sites, self.zone_radius, self.min_spacing, self.crowding, rng, budget);
+        let site = *sites.first()?;
+
+        let distance = |ship_id: &ShipId| game.game_map.calculate_distance(&game.ships[ship_id].position, &site);
+        let closest = candidates.iter().map(&distance).min()?;
+        let ship_id = *candidates.iter()
+            .filter(|ship_id| distance(ship_id) as f64 <= closest as f64 * self.builder_reach + 1.0)
+            .max_by_key(|ship_id| game.ships[*ship_id].halite)?;
+
+        Some(DropoffPlan { ship_id, site, bank_cost: self.bank_cost(game, ship_id, &site) })
+    }
+
+    // Builders to send back to mining: those that have been on their way for `max_build_turns`,
+    // and those whose build the bank can't pay for now that the dropoff would no longer pay off.
+    pub fn give_up_builds(&mut self, game: &Game, fleet: &Fleet) -> Vec<ShipId> {
+        let me = &game.players[game.my_id.0];
+        let mut given_up: Vec<ShipId> = self.builders(fleet).into_iter()
+            .filter(|&(ship_id, site)| {
+                let started = fleet.get(ship_id)
+                    .and_then(|memory| memory.history.iter().rev().find(|transition| transition.to == ShipState::BuildingDropoff(site)))
+                    .map_or(game.turn_number, |transition| transition.turn);
+                let too_late = game.turns_left() < self.min_turns_left && self.bank_cost(game, ship_id, &site) > me.halite;
+                game.turn_number >= started + self.max_build_turns || too_late
+            })
+            .map(|(ship_id, _)| ship_id)
+            .collect();
+        given_up.sort_by_key(|ship_id| ship_id.0);
+
+        if !given_up.is_empty() {
+            self.resume_turn = game.turn_number + self.max_build_turns;
+        }
+        given_up
+    }
+
+    // Halite the bank should keep for dropoffs that are underway, so spawning doesn't spend it.
+    pub fn reserve(&self, game: &Game, fleet: &Fleet) -> usize {
+        self.builders(fleet).iter()
+            .map(|&(ship_id, site)| self.bank_cost(game, ship_id, &site))
+            .sum()
+    }
+
+    pub fn bank_cost(&self, game: &Game, ship_id: ShipId, site: &Position) -> usize {
+        let cargo = game.ships.get(&ship_id).map_or(0, |ship| ship.halite);
+        game.constants.construction_cost(cargo, game.game_map.at_position(site).halite)
+    }
+
+    fn builders(&self, fleet: &Fleet) -> Vec<(ShipId, Position)> {
+        fleet.iter()
+            .filter_map(|(ship_id, memory)| match memory.state {
+                ShipState::BuildingDropoff(site) => Some((ship_id, site)),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hlt::constants::Constants;
+    use hlt::game_map::GameMap;
+    use hlt::player::Player;
+    use hlt::PlayerId;
+    use hlt::simulator::Simulator;
+
+    const SITE: Position = Position { x: 12, y: 12 };
+
+    // Our ship 0 carrying 300 halite on a map with 200 halite on every cell, sent to build at
+    // `SITE` on turn 100.
+    fn building() -> (Game, Fleet, DropoffPolicy, ShipId) {
+        let players = vec![
+            Player::new(PlayerId(0), Position { x: 8, y: 16 }),
+            Player::new(PlayerId(1), Position { x: 24, y: 16 }),
+        ];
+        let mut simulator = Simulator::new(Constants::default_for_map(32), GameMap::new(32, 32, &vec![vec![200; 32]; 32]), players);
+        let ship_id = simulator.add_ship(PlayerId(0), Position { x: 10, y: 12 }, 300);
+        let mut game = Game::from_simulator(&simulator, PlayerId(0));
+        game.turn_number = 100;
+
+        let mut fleet = Fleet::new();
+        fleet.update(&game);
+        fleet.transition(ship_id, ShipState::BuildingDropoff(SITE));
+
+        let mut policy = DropoffPolicy::new(&game.config);
+        policy.max_build_turns = 20;
+        policy.min_turns_left = 150;
+        (game, fleet, policy, ship_id)
+    }
+
+    #[test]
+    fn reserves_what_the_build_takes_from_the_bank() {
+        let (game, mut fleet, policy, ship_id) = building();
+        assert_eq!(policy.bank_cost(&game, ship_id, &SITE), 4000 - 300 - 200);
+        assert_eq!(policy.reserve(&game, &fleet), 3500);
+
+        fleet.transition(ship_id, ShipState::Exploring);
+        assert_eq!(policy.reserve(&game, &fleet), 0);
+    }
+
+    #[test]
+    fn gives_up_a_build_that_takes_too_long() {
+        let (mut game, fleet, mut policy, ship_id) = building();
+
+        game.turn_number = 119;
+        assert!(policy.give_up_builds(&game, &fleet).is_empty());
+
+        game.turn_number = 120;
+        assert_eq!(policy.give_up_builds(&game, &fleet), vec![ship_id]);
+        assert_eq!(policy.resume_turn, 140);
+        assert!(policy.plan(&game, &fleet, &game.clock.turn_budget()).is_none());
+    }
+
+    #[test]
+    fn gives_up_a_late_build_the_bank_cannot_pay_for() {
+        let (mut game, fleet, mut policy, ship_id) = building();
+        policy.max_build_turns = 1000;
+        let max_turns = game.constants.max_turns;
+
+        // With 150 turns left the dropoff still pays off, however long the bank takes.
+        game.turn_number = max_turns - 150;
+        game.players[0].halite = 3499;
+        assert!(policy.give_up_builds(&game, &fleet).is_empty());
+
+        // With 149 left it only goes ahead if the bank can pay for it now.
+        game.turn_number = max_turns - 149;
+        game.players[0].halite = 3500;
+        assert!(policy.give_up_builds(&game, &fleet).is_empty());
+        game.players[0].halite = 3499;
+        assert_eq!(policy.give_up_builds(&game, &fleet), vec![ship_id]);
+    }
+}
@@ -0,0 +1,122 @@
+use hlt::game::Game;
+use hlt::position::Position;
+use hlt::ShipId;
+use std::collections::HashMap;
+use std::collections::HashSet;
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum ShipState {
+    Exploring,
+    Returning,
+    // Heading home at the end of the game, when crashing on our own dropoffs is fine.
+    RushReturn,
+    // Travelling to the given cell to turn into a dropoff there.
+    BuildingDropoff(Position),
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct Transition {
+    pub turn: usize,
+    pub from: ShipState,
+    pub to: ShipState,
+}
+
+pub struct ShipMemory {
+    pub state: ShipState,
+    pub target: Option<Position>,
+    pub first_seen: usize,
+    pub history: Vec<Transition>,
+}
+
+type TransitionHook = Box<dyn FnMut(ShipId, &Transition)>;
+
+// What we remember about each of our ships between turns. Ships show up in the `Exploring` state
+// the first turn they are seen and are forgotten once they are gone from the game, whether they
+// crashed or became a dropoff.
+pub struct Fleet {
+    ships: HashMap<ShipId, ShipMemory>,
+    turn: usize,
+    hooks: Vec<TransitionHook>,
+}
+
+impl Fleet {
+    pub fn new() -> Fleet {
+        Fleet { ships: HashMap::new(), turn: 0, hooks: Vec::new() }
+    }
+
+    // Call once per turn after `update_frame`. Returns the ships that were lost since last turn.
+    pub fn update(&mut self, game: &Game) -> Vec<ShipId> {
+        self.turn = game.turn_number;
+        let alive: HashSet<ShipId> = game.players[game.my_id.0].ship_ids.iter().cloned().collect();
+
+        // Sorted so replays of a game log the same lines in the same order.
+        let mut lost: Vec<ShipId> = self.ships.keys().filter(|ship_id| !alive.contains(ship_id)).cloned().collect();
+        lost.sort_by_key(|ship_id| ship_id.0);
+        for ship_id in &lost {
+            self.ships.remove(ship_id);
+        }
+
+        let turn = self.turn;
+        for ship_id in alive {
+            self.ships.entry(ship_id).or_insert_with(|| ShipMemory {
+                state: ShipState::Exploring,
+                target: None,
+                first_seen: turn,
+                history: Vec::new(),
+            });
+        }
+
+        lost
+    }
+
+    // Ships we don't know about count as exploring, like new ships do.
+    pub fn state(&self, ship_id: ShipId) -> ShipState {
+        self.ships.get(&ship_id).map_or(ShipState::Exploring, |memory| memory.state)
+    }
+
+    pub fn get(&self, ship_id: ShipId) -> Option<&ShipMemory> {
+        self.ships.get(&ship_id)
+    }
+
+    pub fn iter<'a>(&'a self) -> impl Iterator<Item = (ShipId, &'a ShipMemory)> + 'a {
+        self.ships.iter().map(|(ship_id, memory)| (*ship_id, memory))
+    }
+
+    pub fn ships_in_state<'a>(&'a self, state: ShipState) -> impl Iterator<Item = ShipId> + 'a {
+        self.ships.iter().filter(move |&(_, memory)| memory.state == state).map(|(ship_id, _)| *ship_id)
+    }
+
+    // Moves a ship to a new state, recording the change and running the hooks. Setting the state a
+    // ship is already in, or the state of a ship we don't have, does nothing.
+    pub fn transition(&mut self, ship_id: ShipId, state: ShipState) {
+        let transition = match self.ships.get_mut(&ship_id) {
+            Some(ref memory) if memory.state == state => return,
+            Some(memory) => {
+                let transition = Transition { turn: self.turn, from: memory.state, to: state };
+                memory.state = state;
+                memory.history.push(transition);
+                transition
+            },
+            None => return,
+        };
+
+        for hook in &mut self.hooks {
+            hook(ship_id, &transition);
+        }
+    }
+
+    pub fn set_target(&mut self, ship_id: ShipId, target: Option<Position>) {
+        if let Some(memory) = self.ships.get_mut(&ship_id) {
+            memory.target = target;
+        }
+    }
+
+    pub fn target(&self, ship_id: ShipId) -> Option<Position> {
+        self.ships.get(&ship_id).and_then(|memory| memory.target)
+    }
+
+    // Registers a function to run on every state change of every ship.
+    pub fn on_transition<F: FnMut(ShipId, &Transition) + 'static>(&mut self, hook: F) {
+        self.hooks.push(Box::new(hook));
+    }
+}
@@ -1,17 +1,30 @@
+use hlt::clock::TurnClock;
 use hlt::command::Command;
+use hlt::config::StrategyConfig;
 use hlt::constants::Constants;
 use hlt::dropoff::Dropoff;
 use hlt::DropoffId;
 use hlt::game_map::GameMap;
+use hlt::history::History;
 use hlt::input::Input;
 use hlt::log::Log;
 use hlt::map_cell::Structure;
 use hlt::player::Player;
 use hlt::PlayerId;
+use hlt::recording::Recording;
 use hlt::ship::Ship;
 use hlt::ShipId;
+#[cfg(test)]
+use hlt::simulator::Simulator;
+#[cfg(test)]
+use hlt::transport::BufferTransport;
+use hlt::transport::StdioTransport;
+use hlt::transport::Transport;
+use rand::prng::XorShiftRng;
+use rand::SeedableRng;
 use std::cell::RefCell;
 use std::collections::HashMap;
+use std::io::Write;
 use std::ops::DerefMut;
 use std::rc::Rc;
 
@@ -24,27 +37,69 @@ pub struct Game {
     pub ships: HashMap<ShipId, Ship>,
     pub dropoffs: HashMap<DropoffId, Dropoff>,
     pub game_map: GameMap,
+    pub history: History,
+    pub clock: TurnClock,
+    pub config: StrategyConfig,
+    // Every random choice the bot makes draws from this, so a game plays out the same again from
+    // the same seed and input. It sits in a `RefCell` for planners that only get a `&Game`.
+    pub rng: RefCell<XorShiftRng>,
+    transport: Rc<RefCell<Box<dyn Transport>>>,
     input: Input,
 }
 
 impl Game {
     pub fn new() -> Game {
+        Game::with_transport(Box::new(StdioTransport))
+    }
+
+    // Plays over any transport, e.g. a buffer filled by a test or the simulator.
+    pub fn with_transport(transport: Box<dyn Transport>) -> Game {
+        Game::with_recording(transport, None)
+    }
+
+    // Like `with_transport`, copying every line read from the engine into `recording` if there is
+    // one. Feeding that back through a transport plays the game again without the engine.
+    pub fn with_recording(transport: Box<dyn Transport>, recording: Option<Box<dyn Write>>) -> Game {
+        Game::start(transport, recording, true)
+    }
+
+    // The game `player` sees at the start of the simulator's next turn, for testing planners
+    // against a position set up by hand. Nothing is logged to a file.
+    #[cfg(test)]
+    pub fn from_simulator(simulator: &Simulator, player: PlayerId) -> Game {
+        let transport = BufferTransport::new();
+        transport.push_input(&simulator.initial_message(player, 0));
+        transport.push_input(&simulator.frame_message());
+
+        let mut game = Game::start(Box::new(transport), None, false);
+        game.update_frame();
+        game
+    }
+
+    fn start(transport: Box<dyn Transport>, recording: Option<Box<dyn Write>>, log_to_file: bool) -> Game {
         let log = Rc::new(RefCell::new(Log::new()));
-        let mut input = Input::new(&log);
+        let transport = Rc::new(RefCell::new(transport));
+        let mut input = Input::new(&log, &transport);
+        if let Some(recording) = recording {
+            input.record_to(recording);
+        }
         let constants = Constants::new(log.borrow_mut().deref_mut(), &input.read_and_return_line());
 
         input.read_and_parse_line();
         let num_players = input.next_usize();
         let my_id = PlayerId(input.next_usize());
 
-        log.borrow_mut().open(my_id.0);
+        if log_to_file {
+            log.borrow_mut().open(my_id.0);
+        }
 
         let mut players: Vec<Player> = Vec::new();
         for _ in 0..num_players {
             players.push(Player::generate(&mut input));
         }
 
-        let game_map = GameMap::generate(&mut input);
+        let mut game_map = GameMap::generate(&mut input);
+        game_map.update_dropoff_fields(&players, &HashMap::new());
 
         Game {
             log,
@@ -54,13 +109,22 @@ impl Game {
             players,
             ships: HashMap::new(),
             dropoffs: HashMap::new(),
+            config: StrategyConfig::for_map(game_map.width),
             game_map,
+            history: History::new(num_players),
+            clock: TurnClock::new(),
+            rng: RefCell::new(XorShiftRng::seed_from_u64(0)),
+            transport,
             input
         }
     }
 
-    pub fn ready(name: &str) {
-        println!("{}", name);
+    pub fn seed_rng(&mut self, seed: u64) {
+        self.rng = RefCell::new(XorShiftRng::seed_from_u64(seed));
+    }
+
+    pub fn ready(&self, name: &str) {
+        self.transport.borrow_mut().write_line(name);
     }
 
     pub fn update_frame(&mut self) {
@@ -68,6 +132,7 @@ impl Game {
 
         input.read_and_parse_line();
         self.turn_number = input.next_usize();
+        self.clock.start(self.turn_number);
 
         self.log.borrow_mut().log(&format!("=============== TURN {} ================", self.turn_number));
 
@@ -107,17 +172,36 @@ impl Game {
                 self.game_map.at_entity_mut(dropoff).structure = Structure::Dropoff(*dropoff_id);
             }
         }
+
+        self.game_map.update_dropoff_fields(&self.players, &self.dropoffs);
+        self.game_map.update_inspiration(&self.constants, self.players.len(), &self.ships);
+        self.game_map.update_threats(&self.constants, &self.ships, self.my_id);
+        self.history.record(self.turn_number, &self.constants, &self.players, &self.ships, &self.dropoffs, &self.game_map);
     }
 
-    pub fn end_turn(commands: &[Command]) {
+    // Adds a note for replays to the recording of the game, if it is being recorded.
+    pub fn note_in_recording(&self, note: &str) {
+        self.input.note(note);
+    }
+
+    pub fn end_turn(&self, commands: &[Command]) {
+        if self.clock.is_expired() {
+            self.log.borrow_mut().log(&format!("Turn {} ran over its time budget, taking {}ms.", self.turn_number, self.clock.elapsed().as_millis()));
+        }
+        let exhausted_polls = self.clock.exhausted_polls();
+        if !exhausted_polls.is_empty() {
+            self.input.note(&Recording::exhausted_polls_note(self.turn_number, &exhausted_polls));
+        }
+
+        let mut line = String::new();
         for command in commands {
-            print!("{} ", command.0);
+            line.push_str(&command.to_string());
+            line.push(' ');
         }
-        println!();
+        self.transport.borrow_mut().write_line(&line);
     }
 
     pub fn turns_left(&self) -> usize {
-        let turns_left = self.constants.max_turns - self.turn_number;
-        turns_left
+        self.constants.max_turns - self.turn_number
     }
 }
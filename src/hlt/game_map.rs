@@ -1,18 +1,84 @@
+use hlt::clock::Budget;
+use hlt::constants::Constants;
 use hlt::direction::Direction;
+use hlt::dropoff::Dropoff;
+use hlt::DropoffId;
 use hlt::entity::Entity;
 use hlt::input::Input;
 use hlt::map_cell::MapCell;
 use hlt::map_cell::Structure;
+use hlt::player::Player;
+use hlt::PlayerId;
 use hlt::position::Position;
 use hlt::ship::Ship;
+use hlt::ShipId;
+use rand::prng::XorShiftRng;
+use rand::Rng;
 use std::cmp::min;
+use std::cmp::Reverse;
 use std::collections::BinaryHeap;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::collections::VecDeque;
 use std::cmp::Ordering;
 
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum PathMode {
+    // Spend as little halite on moves as possible, taking the fewest turns among equally cheap paths.
+    LeastHalite,
+    // Arrive as soon as possible, spending the least halite among equally short paths.
+    FewestTurns,
+}
+
+pub struct Path {
+    // Every cell visited after the start, ending with the target.
+    pub positions: Vec<Position>,
+    pub directions: Vec<Direction>,
+    pub halite_cost: usize,
+    pub turns: usize,
+}
+
+// How much danger enemies pose to our ships on a cell during the next turn.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum RiskPolicy {
+    // Stay off every cell an enemy ship could move onto.
+    Avoid,
+    // Enter such cells only while carrying less than the lightest enemy ship that could end up
+    // there, so a crash costs them more than it costs us.
+    AcceptIfCarryingLess,
+    // Ignore enemies altogether and move onto their ships when that is where we want to go.
+    Collide,
+}
+
+// Enemy ships that could end the next turn on a cell.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct Threat {
+    pub ships: usize,
+    pub lightest_cargo: usize,
+    // Whether one of them is on the cell now.
+    pub occupied: bool,
+}
+
+// The shipyard or dropoff of a player closest to a cell, and the first move towards it.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct NearestDropoff {
+    pub position: Position,
+    pub distance: usize,
+    pub direction: Direction,
+}
+
 pub struct GameMap {
     pub width: usize,
     pub height: usize,
     cells: Vec<Vec<MapCell>>,
+    // Row-major fields indexed by player id, rebuilt every turn by `update_dropoff_fields`.
+    dropoff_fields: Vec<Vec<NearestDropoff>>,
+    // Row-major counts of other players' ships within the inspiration radius, indexed by player id
+    // and rebuilt every turn by `update_inspiration`.
+    enemies_nearby: Vec<Vec<usize>>,
+    inspiration_ship_count: usize,
+    // Cells enemy ships can reach next turn, rebuilt every turn by `update_threats`.
+    threats: HashMap<Position, Threat>,
 }
 
 impl GameMap {
@@ -26,11 +92,11 @@ impl GameMap {
         &mut self.cells[normalized.y as usize][normalized.x as usize]
     }
 
-    pub fn at_entity(&self, entity: &Entity) -> &MapCell {
+    pub fn at_entity(&self, entity: &dyn Entity) -> &MapCell {
         self.at_position(&entity.position())
     }
 
-    pub fn at_entity_mut(&mut self, entity: &Entity) -> &mut MapCell {
+    pub fn at_entity_mut(&mut self, entity: &dyn Entity) -> &mut MapCell {
         self.at_position_mut(&entity.position())
     }
 
@@ -38,13 +104,13 @@ impl GameMap {
         let normalized_source = self.normalize(source);
         let normalized_target = self.normalize(target);
 
-        let dx = (normalized_source.x - normalized_target.x).abs() as usize;
-        let dy = (normalized_source.y - normalized_target.y).abs() as usize;
+        let dx = (normalized_source.x - normalized_target.x).unsigned_abs() as usize;
+        let dy = (normalized_source.y - normalized_target.y).unsigned_abs() as usize;
 
         let toroidal_dx = min(dx, self.width - dx);
         let toroidal_dy = min(dy, self.height - dy);
 
-        return toroidal_dx + toroidal_dy;
+        toroidal_dx + toroidal_dy
     }
 
     pub fn normalize(&self, position: &Position) -> Position {
@@ -59,8 +125,8 @@ impl GameMap {
         let normalized_source = self.normalize(source);
         let normalized_destination = self.normalize(destination);
 
-        let dx = (normalized_source.x - normalized_destination.x).abs() as usize;
-        let dy = (normalized_source.y - normalized_destination.y).abs() as usize;
+        let dx = (normalized_source.x - normalized_destination.x).unsigned_abs() as usize;
+        let dy = (normalized_source.y - normalized_destination.y).unsigned_abs() as usize;
 
         let wrapped_dx = self.width - dx;
         let wrapped_dy = self.height - dy;
@@ -86,7 +152,7 @@ impl GameMap {
         let ship_position = &ship.position;
 
         // get_unsafe_moves normalizes for us
-        for direction in self.get_unsafe_moves(&ship_position, destination) {
+        for direction in self.get_unsafe_moves(ship_position, destination) {
             let target_pos = ship_position.directional_offset(direction);
             let target_cell = self.at_position_mut(&target_pos);
 
@@ -99,6 +165,88 @@ impl GameMap {
         Direction::Still
     }
 
+    // Finds a path over the wrapped map for `ship`, paying the move cost of every cell left along
+    // the way, with the inspired rate where the ship would be inspired. Cells in `avoided` are never
+    // entered unless they are the target. Returns None when every way to the target is avoided, or
+    // when the budget runs out before the target is reached.
+    pub fn find_path(&self, constants: &Constants, ship: &Ship, target: &Position, mode: PathMode, avoided: &HashSet<Position>, budget: &Budget) -> Option<Path> {
+        let source = self.normalize(&ship.position);
+        let target = self.normalize(target);
+        let index = |position: &Position| position.y as usize * self.width + position.x as usize;
+
+        // Costs are compared as (primary, secondary) pairs whose order depends on the mode. The
+        // remaining distance never overestimates the turns left, which makes it a valid heuristic.
+        let key = |halite: usize, turns: usize| match mode {
+            PathMode::LeastHalite => (halite, turns),
+            PathMode::FewestTurns => (turns, halite),
+        };
+        let heuristic = |position: &Position| key(0, self.calculate_distance(position, &target));
+
+        let num_cells = self.width * self.height;
+        let mut best: Vec<Option<(usize, usize)>> = vec![None; num_cells];
+        let mut came_from: Vec<Option<(Position, Direction)>> = vec![None; num_cells];
+        let mut closed = vec![false; num_cells];
+        let mut open = BinaryHeap::new();
+
+        best[index(&source)] = Some((0, 0));
+        let (first, second) = heuristic(&source);
+        open.push(Reverse((first, second, source.x, source.y)));
+
+        while let Some(Reverse((_, _, x, y))) = open.pop() {
+            if budget.is_exhausted() {
+                return None;
+            }
+
+            let position = Position { x, y };
+            let current = index(&position);
+            if closed[current] {
+                continue;
+            }
+            closed[current] = true;
+
+            if position == target {
+                break;
+            }
+
+            let (halite, turns) = best[current].unwrap();
+            let move_cost = constants.move_cost(self.at_position(&position).halite, self.is_inspired(&position, ship.owner));
+            for direction in Direction::get_all_cardinals() {
+                let next = self.normalize(&position.directional_offset(direction));
+                let next_index = index(&next);
+                if closed[next_index] || (next != target && avoided.contains(&next)) {
+                    continue;
+                }
+
+                let cost = (halite + move_cost, turns + 1);
+                let improves = match best[next_index] {
+                    Some((best_halite, best_turns)) => key(cost.0, cost.1) < key(best_halite, best_turns),
+                    None => true,
+                };
+                if improves {
+                    best[next_index] = Some(cost);
+                    came_from[next_index] = Some((position, direction));
+                    let (cost_first, cost_second) = key(cost.0, cost.1);
+                    let (heuristic_first, heuristic_second) = heuristic(&next);
+                    open.push(Reverse((cost_first + heuristic_first, cost_second + heuristic_second, next.x, next.y)));
+                }
+            }
+        }
+
+        let (halite_cost, turns) = best[index(&target)]?;
+        let mut positions = Vec::with_capacity(turns);
+        let mut directions = Vec::with_capacity(turns);
+        let mut position = target;
+        while let Some((previous, direction)) = came_from[index(&position)] {
+            positions.push(position);
+            directions.push(direction);
+            position = previous;
+        }
+        positions.reverse();
+        directions.reverse();
+
+        Some(Path { positions, directions, halite_cost, turns })
+    }
+
     pub fn most_halite_near_ship_direction(&mut self, position: &Position) -> Option<Direction> {     
         let mut most_halite = 0;
         let mut best_direction = Direction::Still;
@@ -119,14 +267,19 @@ impl GameMap {
         }
     }
 
-    pub fn move_towards_rich_halite(&mut self, position: &Position) -> Direction {
-        let mut best_direction = Direction::Still;
+    // Directions are tried in random order so ties don't always go the same way. When no direction
+    // leads to rich halite nearby, a random one is picked to explore.
+    pub fn move_towards_rich_halite(&mut self, position: &Position, rich_halite: usize, rng: &mut XorShiftRng) -> Direction {
+        let mut directions = Direction::get_all_cardinals();
+        rng.shuffle(&mut directions);
+
+        let mut best_direction = None;
         let mut lowest_distance = 0;
-        for direction in Direction::get_all_cardinals() {
+        for direction in directions.iter().cloned() {
             let mut distance = 0;
             let mut current_pos = *position;
             let mut move_not_found = false;
-            while self.at_position(&current_pos).halite < 25 {
+            while self.at_position(&current_pos).halite < rich_halite {
                 distance += 1;
                 current_pos = current_pos.directional_offset(direction);
                 let cell = self.at_position(&current_pos);
@@ -137,41 +290,238 @@ impl GameMap {
             }
             if (lowest_distance == 0 || distance < lowest_distance) && !move_not_found {
                 lowest_distance = distance;
-                best_direction = direction;
+                best_direction = Some(direction);
             }
         }
-        best_direction
+        best_direction.unwrap_or(directions[0])
     }
 
-    pub fn find_suitable_dropoffs(&mut self) -> Position {
+    // Ranks cells as dropoff sites for `me`, best first, returning at most `count` of them. A
+    // site is worth the halite within its zone, less when it is close to our shipyard and
+    // dropoffs, close to enemy structures or crowded by enemy ships, each of which divides it by
+    // one more `crowding`. Sites closer than the
+    // minimum spacing to our structures or to a better ranked site are left out.
+    // Sites with equal scores are ranked in random order. No sites are returned when the budget
+    // runs out before every cell was rated.
+    #[allow(clippy::too_many_arguments)]
+    pub fn find_suitable_dropoffs(&self, me: PlayerId, ships: &HashMap<ShipId, Ship>, count: usize, zone_radius: usize, min_spacing: usize, crowding: f64, rng: &mut XorShiftRng, budget: &Budget) -> Vec<Position> {
+        let zone_radius = zone_radius as i32;
+        let min_spacing = min_spacing.max(1);
+
+        let mut enemy_ships = vec![0usize; self.width * self.height];
+        for ship in ships.values().filter(|ship| ship.owner != me) {
+            for position in self.zone(&ship.position, zone_radius) {
+                enemy_ships[position.y as usize * self.width + position.x as usize] += 1;
+            }
+        }
+
         let mut heap = BinaryHeap::new();
-        let zone_radius = if self.width < 33 {
-            (3i32
-        } else if self.width < 50 {
-            4i32)
-        } else if self.width < 70 {
-            5i32
-        } else {
-            6i32
-        };
         for x in 0..self.width {
+            if budget.is_exhausted() {
+                return Vec::new();
+            }
             for y in 0..self.height {
-                let mut total_halite = 0;
-                for radx in -zone_radius..zone_radius {
-                    for rady in -zone_radius..zone_radius {
-                        let posx = x as i32 + radx;
-                        let posy = y as i32 + rady;
-                        let pos = Position { x: posx, y: posy };
-                        let norm_pos = self.normalize(&pos);
-                        total_halite += self.at_position(&norm_pos).halite;
+                let position = Position { x: x as i32, y: y as i32 };
+                if self.at_position(&position).has_structure() {
+                    continue;
+                }
+
+                let friendly_distance = self.nearest_dropoff(&position, me).map(|nearest| nearest.distance);
+                if friendly_distance.is_some_and(|distance| distance < min_spacing) {
+                    continue;
+                }
+                let enemy_distance = (0..self.dropoff_fields.len())
+                    .filter(|player| *player != me.0)
+                    .filter_map(|player| self.nearest_dropoff(&position, PlayerId(player)))
+                    .map(|nearest| nearest.distance)
+                    .min();
+                if enemy_distance.is_some_and(|distance| distance <= zone_radius as usize) {
+                    continue;
+                }
+
+                let total_halite: usize = self.zone(&position, zone_radius).iter()
+                    .map(|cell| self.at_position(cell).halite)
+                    .sum();
+
+                // Sites get better the further they are from our structures, up to twice the
+                // minimum spacing, and worse the closer they are to enemy structures.
+                let friendly_factor = friendly_distance.map_or(1.0, |distance| distance.min(2 * min_spacing) as f64 / (2 * min_spacing) as f64);
+                let enemy_factor = enemy_distance.map_or(1.0, |distance| distance.min(min_spacing) as f64 / min_spacing as f64);
+                let crowding_factor = 1.0 / (1.0 + crowding * enemy_ships[y * self.width + x] as f64);
+
+                let score = (total_halite as f64 * friendly_factor * enemy_factor * crowding_factor) as usize;
+                heap.push(HaliteScore { score, tie_breaker: rng.gen(), x: x as i32, y: y as i32 });
+            }
+        }
+
+        let mut sites: Vec<Position> = Vec::new();
+        while let Some(winner) = heap.pop() {
+            if sites.len() >= count {
+                break;
+            }
+            let position = Position { x: winner.x, y: winner.y };
+            if sites.iter().all(|site| self.calculate_distance(site, &position) >= min_spacing) {
+                sites.push(position);
+            }
+        }
+        sites
+    }
+
+    // The cells within `radius` moves of a position.
+    fn zone(&self, center: &Position, radius: i32) -> Vec<Position> {
+        let mut cells = Vec::new();
+        for dy in -radius..radius + 1 {
+            let reach = radius - dy.abs();
+            for dx in -reach..reach + 1 {
+                cells.push(self.normalize(&Position { x: center.x + dx, y: center.y + dy }));
+            }
+        }
+        cells
+    }
+
+    pub fn nearest_dropoff(&self, position: &Position, player: PlayerId) -> Option<NearestDropoff> {
+        let normalized = self.normalize(position);
+        self.dropoff_fields.get(player.0)
+            .map(|field| field[normalized.y as usize * self.width + normalized.x as usize])
+    }
+
+    // Runs one breadth first search per player from their shipyard and dropoffs, so every ship can
+    // look up its way home instead of measuring the distance to each structure.
+    pub fn update_dropoff_fields(&mut self, players: &[Player], dropoffs: &HashMap<DropoffId, Dropoff>) {
+        let mut fields = Vec::with_capacity(players.len());
+        for player in players {
+            let mut sources = vec![player.shipyard.position];
+            sources.extend(player.dropoff_ids.iter().map(|dropoff_id| dropoffs[dropoff_id].position));
+            fields.push(self.distance_field(&sources));
+        }
+        self.dropoff_fields = fields;
+    }
+
+    fn distance_field(&self, sources: &[Position]) -> Vec<NearestDropoff> {
+        let mut field: Vec<Option<NearestDropoff>> = vec![None; self.width * self.height];
+        let mut queue = VecDeque::new();
+
+        for source in sources {
+            let source = self.normalize(source);
+            let index = source.y as usize * self.width + source.x as usize;
+            if field[index].is_none() {
+                field[index] = Some(NearestDropoff { position: source, distance: 0, direction: Direction::Still });
+                queue.push_back(source);
+            }
+        }
+
+        while let Some(position) = queue.pop_front() {
+            let current = field[position.y as usize * self.width + position.x as usize].unwrap();
+            for direction in Direction::get_all_cardinals() {
+                let next = self.normalize(&position.directional_offset(direction));
+                let index = next.y as usize * self.width + next.x as usize;
+                if field[index].is_none() {
+                    // The search spreads away from the structure, so the way back is the opposite move.
+                    field[index] = Some(NearestDropoff {
+                        position: current.position,
+                        distance: current.distance + 1,
+                        direction: direction.invert_direction(),
+                    });
+                    queue.push_back(next);
+                }
+            }
+        }
+
+        field.into_iter().map(|nearest| nearest.unwrap()).collect()
+    }
+
+    // Counts, for every cell and player, the ships of other players within the inspiration radius.
+    // A ship ending its turn on a cell with enough of them is inspired while mining.
+    pub fn update_inspiration(&mut self, constants: &Constants, num_players: usize, ships: &HashMap<ShipId, Ship>) {
+        let mut enemies_nearby = vec![vec![0; self.width * self.height]; num_players];
+        self.inspiration_ship_count = constants.inspiration_ship_count;
+
+        if constants.inspiration_enabled {
+            let radius = constants.inspiration_radius as i32;
+            for ship in ships.values() {
+                for cell in self.zone(&ship.position, radius) {
+                    let index = cell.y as usize * self.width + cell.x as usize;
+                    for (player_index, counts) in enemies_nearby.iter_mut().enumerate() {
+                        if player_index != ship.owner.0 {
+                            counts[index] += 1;
+                        }
                     }
                 }
-                heap.push(HaliteScore { score: total_halite, x: x as i32, y: y as i32});
             }
         }
-        let winner = heap.pop(); {
-      
-        Position { x: winner.x, y: winner.y }
+
+        self.enemies_nearby = enemies_nearby;
+    }
+
+    pub fn enemies_within_inspiration_radius(&self, position: &Position, player: PlayerId) -> usize {
+        let normalized = self.normalize(position);
+        self.enemies_nearby.get(player.0)
+            .map_or(0, |counts| counts[normalized.y as usize * self.width + normalized.x as usize])
+    }
+
+    // Whether a ship of `player` on this cell would be inspired, judging by the ships' positions at
+    // the start of the turn.
+    pub fn is_inspired(&self, position: &Position, player: PlayerId) -> bool {
+        self.inspiration_ship_count > 0 &&
+            self.enemies_within_inspiration_radius(position, player) >= self.inspiration_ship_count
+    }
+
+    // Marks the cells other players' ships can end the next turn on: their own cell, plus the
+    // neighbours when they can pay to move. Cells holding our own structures are left out, since
+    // enemies crashing there only hand us their cargo. Needs the dropoff fields and inspiration
+    // to be up to date.
+    pub fn update_threats(&mut self, constants: &Constants, ships: &HashMap<ShipId, Ship>, me: PlayerId) {
+        let mut threats: HashMap<Position, Threat> = HashMap::new();
+        for ship in ships.values().filter(|ship| ship.owner != me) {
+            let position = self.normalize(&ship.position);
+            let inspired = self.is_inspired(&position, ship.owner);
+            let mut reachable = vec![position];
+            if ship.halite >= constants.move_cost(self.at_position(&position).halite, inspired) {
+                reachable.extend(position.get_surrounding_cardinals().iter().map(|neighbour| self.normalize(neighbour)));
+            }
+
+            for cell in reachable {
+                if self.nearest_dropoff(&cell, me).is_some_and(|nearest| nearest.distance == 0) {
+                    continue;
+                }
+                let threat = threats.entry(cell).or_insert(Threat { ships: 0, lightest_cargo: ship.halite, occupied: false });
+                threat.ships += 1;
+                threat.lightest_cargo = min(threat.lightest_cargo, ship.halite);
+                threat.occupied |= cell == position;
+            }
+        }
+        self.threats = threats;
+    }
+
+    pub fn threat(&self, position: &Position) -> Option<&Threat> {
+        self.threats.get(&self.normalize(position))
+    }
+
+    // Whether a ship carrying `ship_halite` should stay off a cell under the given policy.
+    pub fn is_risky(&self, position: &Position, ship_halite: usize, policy: RiskPolicy) -> bool {
+        match (self.threat(position), policy) {
+            (None, _) | (_, RiskPolicy::Collide) => false,
+            (Some(_), RiskPolicy::Avoid) => true,
+            (Some(threat), RiskPolicy::AcceptIfCarryingLess) => ship_halite >= threat.lightest_cargo,
+        }
+    }
+
+    // The cells a ship carrying `ship_halite` should stay off, for use as the avoided cells of
+    // `find_path`.
+    pub fn risky_cells(&self, ship_halite: usize, policy: RiskPolicy) -> HashSet<Position> {
+        self.threats.keys()
+            .filter(|position| self.is_risky(position, ship_halite, policy))
+            .cloned()
+            .collect()
+    }
+
+    // Total halite on the map, leaving out cells holding less than `min_cell_halite`.
+    pub fn total_halite(&self, min_cell_halite: usize) -> usize {
+        self.cells.iter()
+            .flat_map(|row| row.iter())
+            .map(|cell| cell.halite)
+            .filter(|&halite| halite >= min_cell_halite)
+            .sum()
     }
 
     pub fn update(&mut self, input: &mut Input) {
@@ -194,6 +544,25 @@ impl GameMap {
         }
     }
 
+    // Builds a map from a row-major halite grid, for games that don't come from the engine. A grid
+    // smaller than `width` by `height` panics rather than making a map with missing cells.
+    #[allow(clippy::needless_range_loop)]
+    pub fn new(width: usize, height: usize, halite: &[Vec<usize>]) -> GameMap {
+        let mut cells: Vec<Vec<MapCell>> = Vec::with_capacity(height);
+        for y in 0..height {
+            let mut row: Vec<MapCell> = Vec::with_capacity(width);
+            for x in 0..width {
+                let position = Position { x: x as i32, y: y as i32 };
+                let cell = MapCell { position, halite: halite[y][x], ship: None, structure: Structure::None };
+                row.push(cell);
+            }
+
+            cells.push(row);
+        }
+
+        GameMap { width, height, cells, dropoff_fields: Vec::new(), enemies_nearby: Vec::new(), inspiration_ship_count: 0, threats: HashMap::new() }
+    }
+
     pub fn generate(input: &mut Input) -> GameMap {
         input.read_and_parse_line();
         let width = input.next_usize();
@@ -215,13 +584,14 @@ impl GameMap {
             cells.push(row);
         }
 
-        GameMap { width, height, cells }
+        GameMap { width, height, cells, dropoff_fields: Vec::new(), enemies_nearby: Vec::new(), inspiration_ship_count: 0, threats: HashMap::new() }
     }
 }
 
 #[derive(Eq, PartialEq)]
 struct HaliteScore {
     score: usize,
+    tie_breaker: u64,
     x: i32,
     y: i32
 }
@@ -229,7 +599,9 @@ struct HaliteScore {
 impl Ord for HaliteScore {
     fn cmp(&self, other: &HaliteScore) -> Ordering {
         self.score.cmp(&other.score)
+            .then_with(|| self.tie_breaker.cmp(&other.tie_breaker))
             .then_with(|| self.x.cmp(&other.x))
+            .then_with(|| self.y.cmp(&other.y))
     }
 }
 
@@ -237,4 +609,254 @@ impl PartialOrd for HaliteScore {
     fn partial_cmp(&self, other: &HaliteScore) -> Option<Ordering> {
         Some(self.cmp(other))
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hlt::clock::TurnClock;
+    use std::time::Duration;
+
+    fn empty_map() -> GameMap {
+        GameMap::new(32, 32, &vec![vec![0; 32]; 32])
+    }
+
+    fn ship_at(x: i32, y: i32) -> Ship {
+        Ship::new(PlayerId(0), ShipId(0), Position { x, y }, 0, 1000)
+    }
+
+    fn dropoffs(owner: PlayerId, positions: &[Position]) -> (Player, HashMap<DropoffId, Dropoff>) {
+        let mut player = Player::new(owner, Position { x: 1, y: 1 });
+        let mut dropoffs = HashMap::new();
+        for (index, position) in positions.iter().enumerate() {
+            let id = DropoffId(owner.0 * 100 + index);
+            player.dropoff_ids.push(id);
+            dropoffs.insert(id, Dropoff { owner, id, position: *position });
+        }
+        (player, dropoffs)
+    }
+
+    #[test]
+    fn least_halite_paths_go_around_rich_cells_that_fewest_turns_paths_cross() {
+        let mut game_map = empty_map();
+        game_map.at_position_mut(&Position { x: 1, y: 0 }).halite = 1000;
+        let constants = Constants::default_for_map(32);
+        let clock = TurnClock::new();
+        let ship = ship_at(0, 0);
+        let target = Position { x: 2, y: 0 };
+
+        let cheap = game_map.find_path(&constants, &ship, &target, PathMode::LeastHalite, &HashSet::new(), &clock.turn_budget()).unwrap();
+        assert_eq!((cheap.halite_cost, cheap.turns), (0, 4));
+        assert!(!cheap.positions.contains(&Position { x: 1, y: 0 }));
+        assert_eq!(cheap.positions.last(), Some(&target));
+
+        let fast = game_map.find_path(&constants, &ship, &target, PathMode::FewestTurns, &HashSet::new(), &clock.turn_budget()).unwrap();
+        assert_eq!((fast.halite_cost, fast.turns), (100, 2));
+        assert_eq!(fast.directions, vec![Direction::East, Direction::East]);
+    }
+
+    #[test]
+    fn paths_wrap_around_the_map_edge() {
+        let game_map = empty_map();
+        let constants = Constants::default_for_map(32);
+        let clock = TurnClock::new();
+
+        let path = game_map.find_path(&constants, &ship_at(0, 5), &Position { x: 31, y: 5 }, PathMode::FewestTurns, &HashSet::new(), &clock.turn_budget()).unwrap();
+        assert_eq!(path.directions, vec![Direction::West]);
+        assert_eq!(path.positions, vec![Position { x: 31, y: 5 }]);
+        assert_eq!(path.turns, 1);
+    }
+
+    #[test]
+    fn avoided_cells_are_not_entered_unless_they_are_the_target() {
+        let game_map = empty_map();
+        let constants = Constants::default_for_map(32);
+        let clock = TurnClock::new();
+        let target = Position { x: 2, y: 0 };
+        let avoided: HashSet<Position> = vec![Position { x: 1, y: 0 }, target].into_iter().collect();
+
+        let path = game_map.find_path(&constants, &ship_at(0, 0), &target, PathMode::FewestTurns, &avoided, &clock.turn_budget()).unwrap();
+        assert_eq!(path.turns, 4);
+        assert_eq!(path.positions.last(), Some(&target));
+        assert!(!path.positions.contains(&Position { x: 1, y: 0 }));
+    }
+
+    #[test]
+    fn no_path_to_a_target_that_is_walled_off() {
+        let game_map = empty_map();
+        let constants = Constants::default_for_map(32);
+        let clock = TurnClock::new();
+        let target = Position { x: 2, y: 0 };
+        let avoided: HashSet<Position> = target.get_surrounding_cardinals().iter().map(|cell| game_map.normalize(cell)).collect();
+
+        let path = game_map.find_path(&constants, &ship_at(0, 0), &target, PathMode::LeastHalite, &avoided, &clock.turn_budget());
+        assert!(path.is_none());
+    }
+
+    #[test]
+    fn no_path_once_the_budget_runs_out() {
+        let game_map = empty_map();
+        let constants = Constants::default_for_map(32);
+        let clock = TurnClock::with_deadline(Duration::from_secs(0));
+
+        let path = game_map.find_path(&constants, &ship_at(0, 0), &Position { x: 2, y: 0 }, PathMode::LeastHalite, &HashSet::new(), &clock.turn_budget());
+        assert!(path.is_none());
+    }
+
+    #[test]
+    fn dropoff_distances_wrap_around_the_map_edge() {
+        let mut game_map = empty_map();
+        let (player, dropoffs) = dropoffs(PlayerId(0), &[]);
+        game_map.update_dropoff_fields(&[player], &dropoffs);
+
+        let nearest = game_map.nearest_dropoff(&Position { x: 30, y: 31 }, PlayerId(0)).unwrap();
+        assert_eq!(nearest.position, Position { x: 1, y: 1 });
+        assert_eq!(nearest.distance, 5);
+        assert!(nearest.direction == Direction::East || nearest.direction == Direction::South);
+    }
+
+    #[test]
+    fn following_the_directions_reaches_the_nearest_dropoff() {
+        let mut game_map = empty_map();
+        let (player, dropoffs) = dropoffs(PlayerId(0), &[Position { x: 12, y: 10 }]);
+        game_map.update_dropoff_fields(&[player], &dropoffs);
+
+        let start = game_map.nearest_dropoff(&Position { x: 10, y: 10 }, PlayerId(0)).unwrap();
+        assert_eq!(start.position, Position { x: 12, y: 10 });
+        assert_eq!(start.distance, 2);
+        assert_eq!(start.direction, Direction::East);
+
+        let mut position = Position { x: 20, y: 25 };
+        let expected = game_map.nearest_dropoff(&position, PlayerId(0)).unwrap();
+        for steps_left in (0..expected.distance).rev() {
+            let nearest = game_map.nearest_dropoff(&position, PlayerId(0)).unwrap();
+            position = game_map.normalize(&position.directional_offset(nearest.direction));
+            assert_eq!(game_map.nearest_dropoff(&position, PlayerId(0)).unwrap().distance, steps_left);
+        }
+        assert_eq!(position, expected.position);
+    }
+
+    #[test]
+    fn ties_between_dropoffs_lead_to_one_of_them() {
+        let mut game_map = empty_map();
+        let (player, dropoffs) = dropoffs(PlayerId(0), &[Position { x: 5, y: 1 }]);
+        game_map.update_dropoff_fields(&[player], &dropoffs);
+
+        let nearest = game_map.nearest_dropoff(&Position { x: 3, y: 1 }, PlayerId(0)).unwrap();
+        assert_eq!(nearest.distance, 2);
+        assert!(nearest.position == Position { x: 1, y: 1 } || nearest.position == Position { x: 5, y: 1 });
+        let step = game_map.normalize(&Position { x: 3, y: 1 }.directional_offset(nearest.direction));
+        assert_eq!(game_map.calculate_distance(&step, &nearest.position), 1);
+    }
+
+    #[test]
+    fn other_players_dropoffs_are_not_ours() {
+        let mut game_map = empty_map();
+        let (mine, mut all_dropoffs) = dropoffs(PlayerId(0), &[]);
+        let (mut theirs, their_dropoffs) = dropoffs(PlayerId(1), &[Position { x: 20, y: 20 }]);
+        theirs.shipyard.position = Position { x: 16, y: 1 };
+        all_dropoffs.extend(their_dropoffs);
+        game_map.update_dropoff_fields(&[mine, theirs], &all_dropoffs);
+
+        assert_eq!(game_map.nearest_dropoff(&Position { x: 20, y: 21 }, PlayerId(0)).unwrap().position, Position { x: 1, y: 1 });
+        assert_eq!(game_map.nearest_dropoff(&Position { x: 20, y: 21 }, PlayerId(1)).unwrap().distance, 1);
+        assert!(game_map.nearest_dropoff(&Position { x: 20, y: 21 }, PlayerId(2)).is_none());
+    }
+
+    fn ships(ships: &[(usize, i32, i32)]) -> HashMap<ShipId, Ship> {
+        ships.iter().enumerate()
+            .map(|(id, &(owner, x, y))| (ShipId(id), Ship::new(PlayerId(owner), ShipId(id), Position { x, y }, 0, 1000)))
+            .collect()
+    }
+
+    #[test]
+    fn inspiration_radius_wraps_around_the_map_edge() {
+        let mut game_map = empty_map();
+        let constants = Constants::default_for_map(32);
+        game_map.update_inspiration(&constants, 2, &ships(&[(1, 30, 0), (1, 0, 30)]));
+
+        assert_eq!(game_map.enemies_within_inspiration_radius(&Position { x: 1, y: 1 }, PlayerId(0)), 2);
+        assert!(game_map.is_inspired(&Position { x: 1, y: 1 }, PlayerId(0)));
+        assert_eq!(game_map.enemies_within_inspiration_radius(&Position { x: 2, y: 1 }, PlayerId(0)), 0);
+        assert!(!game_map.is_inspired(&Position { x: 2, y: 1 }, PlayerId(0)));
+    }
+
+    #[test]
+    fn inspiration_needs_enough_enemy_ships() {
+        let mut game_map = empty_map();
+        let constants = Constants::default_for_map(32);
+        let position = Position { x: 10, y: 10 };
+
+        game_map.update_inspiration(&constants, 2, &ships(&[(1, 12, 10), (0, 10, 12), (0, 10, 8)]));
+        assert!(!game_map.is_inspired(&position, PlayerId(0)));
+        assert!(game_map.is_inspired(&position, PlayerId(1)));
+
+        game_map.update_inspiration(&constants, 2, &ships(&[(1, 12, 10), (1, 10, 12)]));
+        assert!(game_map.is_inspired(&position, PlayerId(0)));
+        assert!(!game_map.is_inspired(&position, PlayerId(1)));
+    }
+
+    #[test]
+    fn nobody_is_inspired_when_inspiration_is_disabled() {
+        let mut game_map = empty_map();
+        let mut constants = Constants::default_for_map(32);
+        constants.inspiration_enabled = false;
+        game_map.update_inspiration(&constants, 2, &ships(&[(1, 12, 10), (1, 10, 12), (1, 10, 10)]));
+
+        assert_eq!(game_map.enemies_within_inspiration_radius(&Position { x: 10, y: 10 }, PlayerId(0)), 0);
+        assert!(!game_map.is_inspired(&Position { x: 10, y: 10 }, PlayerId(0)));
+    }
+
+    // Enemy ships at (10, 10) and (12, 10) that can pay to move, one at (20, 20) that can't and one
+    // next to our shipyard at (8, 16), plus one of ours, on a map with 100 halite on every cell.
+    fn threatened_map() -> GameMap {
+        let mut game_map = GameMap::new(32, 32, &vec![vec![100; 32]; 32]);
+        let constants = Constants::default_for_map(32);
+        let players = vec![Player::new(PlayerId(0), Position { x: 8, y: 16 }), Player::new(PlayerId(1), Position { x: 24, y: 16 })];
+        let ships = ships(&[(1, 10, 10), (1, 12, 10), (1, 20, 20), (1, 8, 15), (0, 3, 3)]);
+        let ships = ships.into_iter()
+            .map(|(ship_id, mut ship)| {
+                ship.halite = [200, 50, 5, 500, 0][ship_id.0];
+                (ship_id, ship)
+            })
+            .collect();
+        game_map.update_dropoff_fields(&players, &HashMap::new());
+        game_map.update_inspiration(&constants, 2, &ships);
+        game_map.update_threats(&constants, &ships, PlayerId(0));
+        game_map
+    }
+
+    #[test]
+    fn threats_cover_the_cells_enemies_can_reach() {
+        let game_map = threatened_map();
+
+        assert_eq!(game_map.threat(&Position { x: 10, y: 10 }), Some(&Threat { ships: 1, lightest_cargo: 200, occupied: true }));
+        assert_eq!(game_map.threat(&Position { x: 11, y: 10 }), Some(&Threat { ships: 2, lightest_cargo: 50, occupied: false }));
+        assert_eq!(game_map.threat(&Position { x: 20, y: 20 }), Some(&Threat { ships: 1, lightest_cargo: 5, occupied: true }));
+        assert_eq!(game_map.threat(&Position { x: 21, y: 20 }), None);
+        assert_eq!(game_map.threat(&Position { x: 3, y: 4 }), None);
+        // Enemies crashing into our shipyard only hand us their cargo.
+        assert_eq!(game_map.threat(&Position { x: 8, y: 16 }), None);
+        assert!(game_map.threat(&Position { x: 8, y: 14 }).is_some());
+    }
+
+    #[test]
+    fn each_risk_policy_classifies_threatened_cells() {
+        let game_map = threatened_map();
+        let contested = Position { x: 11, y: 10 };
+        let quiet = Position { x: 3, y: 4 };
+
+        assert!(game_map.is_risky(&contested, 0, RiskPolicy::Avoid));
+        assert!(!game_map.is_risky(&contested, 49, RiskPolicy::AcceptIfCarryingLess));
+        assert!(game_map.is_risky(&contested, 50, RiskPolicy::AcceptIfCarryingLess));
+        assert!(!game_map.is_risky(&contested, 1000, RiskPolicy::Collide));
+        for policy in &[RiskPolicy::Avoid, RiskPolicy::AcceptIfCarryingLess, RiskPolicy::Collide] {
+            assert!(!game_map.is_risky(&quiet, 1000, *policy));
+        }
+
+        let risky: HashSet<Position> = vec![Position { x: 20, y: 20 }].into_iter().collect();
+        assert_eq!(game_map.risky_cells(49, RiskPolicy::AcceptIfCarryingLess), risky);
+        assert!(game_map.risky_cells(1000, RiskPolicy::Collide).is_empty());
+        assert_eq!(game_map.risky_cells(0, RiskPolicy::Avoid).len(), 5 + 4 + 1 + 4);
+    }
+}
@@ -0,0 +1,299 @@
+use hlt::constants::Constants;
+use hlt::dropoff::Dropoff;
+use hlt::DropoffId;
+use hlt::game_map::GameMap;
+use hlt::map_cell::Structure;
+use hlt::player::Player;
+use hlt::PlayerId;
+use hlt::position::Position;
+use hlt::ship::Ship;
+use hlt::ShipId;
+use std::collections::HashMap;
+use std::collections::HashSet;
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum DeathCause {
+    Collision,
+    BecameDropoff,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct ShipSnapshot {
+    pub turn: usize,
+    pub position: Position,
+    pub halite: usize,
+    // Halite on the ship's cell at the start of the turn and whether the ship was inspired there,
+    // which together set what moving off it costs.
+    pub cell_halite: usize,
+    pub inspired: bool,
+}
+
+pub struct ShipHistory {
+    pub id: ShipId,
+    pub owner: PlayerId,
+    pub birth_turn: usize,
+    // The first turn the ship was missing from the game.
+    pub death_turn: Option<usize>,
+    pub death_cause: Option<DeathCause>,
+    pub snapshots: Vec<ShipSnapshot>,
+    // Halite the ship dropped off at its owner's shipyard or dropoffs over its life.
+    pub delivered: usize,
+}
+
+impl ShipHistory {
+    pub fn is_alive(&self) -> bool {
+        self.death_turn.is_none()
+    }
+
+    pub fn last_snapshot(&self) -> &ShipSnapshot {
+        self.snapshots.last().expect("Error: history: ship history without snapshots.")
+    }
+
+    pub fn snapshot_at(&self, turn: usize) -> Option<&ShipSnapshot> {
+        if turn < self.birth_turn {
+            return None;
+        }
+        self.snapshots.get(turn - self.birth_turn)
+    }
+
+    // Delivered halite minus what the ship cost to build. Negative until the ship paid for itself.
+    pub fn profit(&self, ship_cost: usize) -> i64 {
+        self.delivered as i64 - ship_cost as i64
+    }
+
+    pub fn delivered_per_turn(&self) -> f64 {
+        self.delivered as f64 / self.snapshots.len().max(1) as f64
+    }
+}
+
+// Everything seen over the course of the game, for all players. `Game` records a turn every time
+// it reads a frame, since the frame itself only holds what is alive right now.
+pub struct History {
+    pub ships: HashMap<ShipId, ShipHistory>,
+    // Bank halite of each player at the start of every recorded turn, indexed by player id.
+    pub player_halite: Vec<Vec<usize>>,
+    first_turn: Option<usize>,
+    // The dropoffs of the previously recorded turn, to tell which ones were just built.
+    known_dropoffs: HashSet<DropoffId>,
+}
+
+impl History {
+    pub fn new(num_players: usize) -> History {
+        History { ships: HashMap::new(), player_halite: vec![Vec::new(); num_players], first_turn: None, known_dropoffs: HashSet::new() }
+    }
+
+    pub fn record(
+        &mut self,
+        turn: usize,
+        constants: &Constants,
+        players: &[Player],
+        ships: &HashMap<ShipId, Ship>,
+        dropoffs: &HashMap<DropoffId, Dropoff>,
+        game_map: &GameMap)
+    {
+        if self.first_turn.is_none() {
+            self.first_turn = Some(turn);
+        }
+
+        for player in players {
+            self.player_halite[player.id.0].push(player.halite);
+        }
+
+        // Ships only leave the game by crashing or by becoming a dropoff where they stood. A ship that
+        // crashes on one of its owner's older dropoffs didn't build it.
+        let new_dropoffs: Vec<&Dropoff> = dropoffs.values()
+            .filter(|dropoff| !self.known_dropoffs.contains(&dropoff.id))
+            .collect();
+        for history in self.ships.values_mut().filter(|history| history.is_alive()) {
+            if ships.contains_key(&history.id) {
+                continue;
+            }
+            let last_position = history.last_snapshot().position;
+            let became_dropoff = new_dropoffs.iter()
+                .any(|dropoff| dropoff.owner == history.owner && game_map.normalize(&dropoff.position) == last_position);
+
+            history.death_turn = Some(turn);
+            history.death_cause = Some(if became_dropoff { DeathCause::BecameDropoff } else { DeathCause::Collision });
+        }
+
+        for ship in ships.values() {
+            let position = game_map.normalize(&ship.position);
+            let snapshot = ShipSnapshot {
+                turn,
+                position,
+                halite: ship.halite,
+                cell_halite: game_map.at_position(&position).halite,
+                inspired: game_map.is_inspired(&position, ship.owner),
+            };
+
+            let history = self.ships.entry(ship.id).or_insert_with(|| ShipHistory {
+                id: ship.id,
+                owner: ship.owner,
+                birth_turn: turn,
+                death_turn: None,
+                death_cause: None,
+                snapshots: Vec::new(),
+                delivered: 0,
+            });
+
+            // A ship that arrives on one of its owner's structures hands over its whole cargo,
+            // minus what it paid to leave the previous cell.
+            if let Some(previous) = history.snapshots.last().cloned() {
+                let at_home = match game_map.at_position(&position).structure {
+                    Structure::Shipyard(owner) => owner == ship.owner,
+                    Structure::Dropoff(dropoff_id) => dropoffs.get(&dropoff_id).is_some_and(|dropoff| dropoff.owner == ship.owner),
+                    Structure::None => false,
+                };
+                if at_home && previous.position != position {
+                    let move_cost = constants.move_cost(previous.cell_halite, previous.inspired);
+                    history.delivered += previous.halite.saturating_sub(move_cost);
+                }
+            }
+
+            history.snapshots.push(snapshot);
+        }
+
+        self.known_dropoffs = dropoffs.keys().cloned().collect();
+    }
+
+    pub fn player_halite_at(&self, player: PlayerId, turn: usize) -> Option<usize> {
+        let first_turn = self.first_turn?;
+        if turn < first_turn {
+            return None;
+        }
+        self.player_halite.get(player.0).and_then(|halite| halite.get(turn - first_turn)).cloned()
+    }
+
+    pub fn ships_lost_on(&self, turn: usize) -> Vec<&ShipHistory> {
+        self.ships.values().filter(|history| history.death_turn == Some(turn)).collect()
+    }
+
+    // Groups the ships that crashed going into `turn` by the collision they were part of. Frames
+    // don't say where crashes happen, so ships are grouped when their last positions were close
+    // enough for both to reach the same cell, i.e. at most two cells apart.
+    pub fn collisions_on(&self, turn: usize, game_map: &GameMap) -> Vec<Vec<ShipId>> {
+        let crashed: Vec<&ShipHistory> = self.ships_lost_on(turn).into_iter()
+            .filter(|history| history.death_cause == Some(DeathCause::Collision))
+            .collect();
+
+        let mut groups: Vec<Vec<ShipId>> = Vec::new();
+        let mut group_positions: Vec<Vec<Position>> = Vec::new();
+        for history in crashed {
+            let position = history.last_snapshot().position;
+            let existing = group_positions.iter()
+                .position(|positions| positions.iter().any(|other| game_map.calculate_distance(&position, other) <= 2));
+            match existing {
+                Some(index) => {
+                    groups[index].push(history.id);
+                    group_positions[index].push(position);
+                },
+                None => {
+                    groups.push(vec![history.id]);
+                    group_positions.push(vec![position]);
+                },
+            }
+        }
+        groups
+    }
+
+    // Collisions going into `turn` that involved one of our ships and a ship of another player.
+    pub fn collisions_with_enemies_on(&self, turn: usize, game_map: &GameMap, me: PlayerId) -> Vec<Vec<ShipId>> {
+        self.collisions_on(turn, game_map).into_iter()
+            .filter(|group| {
+                let ours = group.iter().any(|ship_id| self.ships[ship_id].owner == me);
+                let theirs = group.iter().any(|ship_id| self.ships[ship_id].owner != me);
+                ours && theirs
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ship(owner: usize, id: usize, x: i32, y: i32, halite: usize) -> (ShipId, Ship) {
+        (ShipId(id), Ship::new(PlayerId(owner), ShipId(id), Position { x, y }, halite, 1000))
+    }
+
+    fn dropoff(owner: usize, id: usize, x: i32, y: i32) -> (DropoffId, Dropoff) {
+        (DropoffId(id), Dropoff { owner: PlayerId(owner), id: DropoffId(id), position: Position { x, y } })
+    }
+
+    fn setup() -> (Constants, Vec<Player>, GameMap) {
+        let players = vec![
+            Player::new(PlayerId(0), Position { x: 8, y: 8 }),
+            Player::new(PlayerId(1), Position { x: 24, y: 8 }),
+        ];
+        let mut game_map = GameMap::new(32, 32, &vec![vec![200; 32]; 32]);
+        for player in &players {
+            game_map.at_position_mut(&player.shipyard.position).structure = Structure::Shipyard(player.id);
+        }
+        (Constants::default_for_map(32), players, game_map)
+    }
+
+    #[test]
+    fn ships_are_born_and_die_on_the_turns_they_appear_and_vanish() {
+        let (constants, players, game_map) = setup();
+        let mut history = History::new(2);
+
+        history.record(3, &constants, &players, &vec![ship(0, 0, 5, 5, 0)].into_iter().collect(), &HashMap::new(), &game_map);
+        history.record(4, &constants, &players, &vec![ship(0, 0, 5, 5, 0), ship(1, 1, 6, 5, 0)].into_iter().collect(), &HashMap::new(), &game_map);
+        history.record(5, &constants, &players, &HashMap::new(), &HashMap::new(), &game_map);
+
+        assert_eq!((history.ships[&ShipId(0)].birth_turn, history.ships[&ShipId(0)].death_turn), (3, Some(5)));
+        assert_eq!((history.ships[&ShipId(1)].birth_turn, history.ships[&ShipId(1)].death_turn), (4, Some(5)));
+        assert_eq!(history.ships[&ShipId(0)].snapshot_at(4).map(|snapshot| snapshot.turn), Some(4));
+        let mut collisions = history.collisions_on(5, &game_map);
+        assert_eq!(collisions.len(), 1);
+        collisions[0].sort_by_key(|ship_id| ship_id.0);
+        assert_eq!(collisions[0], vec![ShipId(0), ShipId(1)]);
+    }
+
+    #[test]
+    fn only_a_dropoff_built_this_turn_marks_a_conversion() {
+        let (constants, players, game_map) = setup();
+        let mut history = History::new(2);
+        let old_dropoff: HashMap<DropoffId, Dropoff> = vec![dropoff(0, 0, 12, 12)].into_iter().collect();
+
+        // Ship 0 sits where a dropoff is about to be built, ship 1 on a dropoff that was already there.
+        let ships = vec![ship(0, 0, 5, 5, 0), ship(0, 1, 12, 12, 0)].into_iter().collect();
+        history.record(1, &constants, &players, &ships, &old_dropoff, &game_map);
+
+        let both_dropoffs = vec![dropoff(0, 0, 12, 12), dropoff(0, 1, 5, 5)].into_iter().collect();
+        history.record(2, &constants, &players, &HashMap::new(), &both_dropoffs, &game_map);
+
+        assert_eq!(history.ships[&ShipId(0)].death_cause, Some(DeathCause::BecameDropoff));
+        assert_eq!(history.ships[&ShipId(1)].death_cause, Some(DeathCause::Collision));
+        assert_eq!(history.collisions_on(2, &game_map), vec![vec![ShipId(1)]]);
+    }
+
+    #[test]
+    fn arriving_home_delivers_the_cargo_less_the_move_cost() {
+        let (constants, players, game_map) = setup();
+        let mut history = History::new(2);
+
+        history.record(1, &constants, &players, &vec![ship(0, 0, 8, 9, 500)].into_iter().collect(), &HashMap::new(), &game_map);
+        history.record(2, &constants, &players, &vec![ship(0, 0, 8, 8, 0)].into_iter().collect(), &HashMap::new(), &game_map);
+        history.record(3, &constants, &players, &vec![ship(0, 0, 8, 8, 0)].into_iter().collect(), &HashMap::new(), &game_map);
+
+        assert_eq!(history.ships[&ShipId(0)].delivered, 500 - 20);
+    }
+
+    #[test]
+    fn inspired_ships_pay_the_inspired_rate_on_the_way_home() {
+        let (mut constants, players, mut game_map) = setup();
+        constants.inspired_move_cost_ratio = 20;
+        let mut history = History::new(2);
+
+        let mut ships: HashMap<ShipId, Ship> = vec![ship(0, 0, 8, 9, 500), ship(1, 1, 8, 11, 0), ship(1, 2, 10, 9, 0)].into_iter().collect();
+        game_map.update_inspiration(&constants, 2, &ships);
+        history.record(1, &constants, &players, &ships, &HashMap::new(), &game_map);
+
+        ships.insert(ShipId(0), ship(0, 0, 8, 8, 0).1);
+        game_map.update_inspiration(&constants, 2, &ships);
+        history.record(2, &constants, &players, &ships, &HashMap::new(), &game_map);
+
+        assert_eq!(history.ships[&ShipId(0)].delivered, 500 - 10);
+    }
+}
@@ -1,32 +1,63 @@
 use hlt::log::Log;
+use hlt::transport::Transport;
 use std::cell::RefCell;
-use std::io::stdin;
+use std::io::Write;
 use std::process::exit;
 use std::rc::Rc;
 use std::str::FromStr;
 
 pub struct Input {
     log: Rc<RefCell<Log>>,
+    transport: Rc<RefCell<Box<dyn Transport>>>,
     tokens: Vec<String>,
     current_token: usize,
+    // Gets a copy of every line read, so the game can be replayed from it later. It sits in a
+    // `RefCell` so notes can be added to it from a `&Game`.
+    recording: RefCell<Option<Box<dyn Write>>>,
 }
 
 impl Input {
-    pub fn new(log: &Rc<RefCell<Log>>) -> Input {
-        Input { log: log.clone(), tokens: Vec::new(), current_token: 0 }
+    pub fn new(log: &Rc<RefCell<Log>>, transport: &Rc<RefCell<Box<dyn Transport>>>) -> Input {
+        Input { log: log.clone(), transport: transport.clone(), tokens: Vec::new(), current_token: 0, recording: RefCell::new(None) }
+    }
+
+    pub fn record_to(&mut self, recording: Box<dyn Write>) {
+        self.recording = RefCell::new(Some(recording));
     }
 
     pub fn read_and_return_line(&mut self) -> String {
-        let mut buf = String::new();
-        match stdin().read_line(&mut buf) {
-            Ok(_) => (),
-            Err(_) => {
+        let line = self.transport.borrow_mut().read_line();
+        match line {
+            Some(buf) => {
+                self.record(&buf);
+                buf
+            },
+            None => {
                 self.log.borrow_mut().log("Input connection from server closed. Exiting...");
                 self.log.borrow_mut().flush();
                 exit(0);
             },
         }
-        buf
+    }
+
+    // Lines are flushed one by one so the recording holds everything up to a crash. A recording
+    // that can't be written isn't worth losing the game over, so it is dropped instead.
+    fn record(&self, line: &str) {
+        let mut recording = self.recording.borrow_mut();
+        let failed = match recording.as_mut() {
+            Some(writer) => writeln!(writer, "{}", line).and_then(|_| writer.flush()).err(),
+            None => None,
+        };
+        if let Some(e) = failed {
+            self.log.borrow_mut().log(&format!("Stopped recording the input: {}", e));
+            *recording = None;
+        }
+    }
+
+    // Adds a line of the bot's own to the recording, if there is one. See `Recording` for the
+    // notes a replay understands.
+    pub fn note(&self, note: &str) {
+        self.record(note);
     }
 
     pub fn read_and_parse_line(&mut self) {
@@ -20,7 +20,7 @@ impl Log {
         }
 
         let filename = format!("bot-{}.log", bot_id);
-        let file = File::create(&filename).expect(&format!("Couldn't open file {} for logging!", &filename));
+        let file = File::create(&filename).unwrap_or_else(|_| panic!("Couldn't open file {} for logging!", &filename));
         self.file = Some(file);
 
         self.dump_log_buffer();
@@ -29,12 +29,9 @@ impl Log {
     }
 
     pub fn log(&mut self, message: &str) {
-        match &mut self.file {
-            Some(file) => {
-                writeln!(file, "{}", message).unwrap();
-                return;
-            },
-            None => ()
+        if let Some(file) = &mut self.file {
+            writeln!(file, "{}", message).unwrap();
+            return;
         }
 
         match &mut self.log_buffer {
@@ -46,9 +43,8 @@ impl Log {
     }
 
     pub fn flush(&mut self) {
-        match &mut self.file {
-            Some(file) => { file.flush().unwrap(); },
-            None => (),
+        if let Some(file) = &mut self.file {
+            file.flush().unwrap();
         }
     }
 
@@ -56,7 +52,7 @@ impl Log {
         if self.file.is_none() {
             let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
             let filename = format!("bot-unknown-{}.log", timestamp.as_secs());
-            let file = File::create(&filename).expect(&format!("Couldn't open file {} for logging!", &filename));
+            let file = File::create(&filename).unwrap_or_else(|_| panic!("Couldn't open file {} for logging!", &filename));
             self.file = Some(file);
         }
 
@@ -79,13 +75,10 @@ impl Log {
             None => panic!("Error: file should exist!")
         };
 
-        match &self.log_buffer {
-            Some(log_buffer) => {
-                for message in log_buffer {
-                    writeln!(file, "{}", message).unwrap();
-                }
+        if let Some(log_buffer) = &self.log_buffer {
+            for message in log_buffer {
+                writeln!(file, "{}", message).unwrap();
             }
-            None => ()
         }
     }
 }
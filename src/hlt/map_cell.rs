@@ -37,10 +37,7 @@ pub enum Structure {
 
 impl Structure {
     pub fn is_some(&self) -> bool {
-        match *self {
-            Structure::None => false,
-            _ => true,
-        }
+        !matches!(*self, Structure::None)
     }
 
     pub fn is_none(&self) -> bool {
@@ -0,0 +1,210 @@
+use hlt::game_map::GameMap;
+use hlt::player::Player;
+use hlt::PlayerId;
+use hlt::position::Position;
+use rand::prng::XorShiftRng;
+use rand::Rng;
+use rand::SeedableRng;
+
+const PERSISTENCE: f64 = 0.7;
+const FACTOR_EXP_1: f64 = 2.0;
+const MIN_CELL_PRODUCTION: usize = 900;
+const MAX_CELL_PRODUCTION: usize = 1000;
+
+// Generates maps the way the official engine's fractal value noise generator does: a single tile
+// of noise is generated and mirrored into every player's quadrant, so each player gets the same
+// halite around its shipyard. Two player maps are split left/right and four player maps into
+// quadrants. The same seed always produces the same map.
+pub struct MapGenerator {
+    pub seed: u64,
+    pub width: usize,
+    pub height: usize,
+    pub num_players: usize,
+}
+
+impl MapGenerator {
+    pub fn new(seed: u64, width: usize, height: usize, num_players: usize) -> MapGenerator {
+        if num_players != 2 && num_players != 4 {
+            panic!("Error: map generator: only 2 and 4 player maps are supported, got {}.", num_players);
+        }
+
+        if !(32..=64).contains(&width) || !(32..=64).contains(&height) || !width.is_multiple_of(2) || !height.is_multiple_of(2) {
+            panic!("Error: map generator: map must be an even size between 32x32 and 64x64, got {}x{}.", width, height);
+        }
+
+        MapGenerator { seed, width, height, num_players }
+    }
+
+    pub fn generate(&self) -> (GameMap, Vec<Player>) {
+        let mut rng = XorShiftRng::seed_from_u64(self.seed);
+
+        let tile_width = self.width / 2;
+        let tile_height = if self.num_players == 4 { self.height / 2 } else { self.height };
+        let tile = MapGenerator::generate_tile(&mut rng, tile_width, tile_height);
+
+        let mut halite: Vec<Vec<usize>> = Vec::with_capacity(self.height);
+        for y in 0..self.height {
+            let tile_y = if y < tile_height { y } else { self.height - 1 - y };
+
+            let mut row: Vec<usize> = Vec::with_capacity(self.width);
+            for x in 0..self.width {
+                let tile_x = if x < tile_width { x } else { self.width - 1 - x };
+                row.push(tile[tile_y][tile_x]);
+            }
+
+            halite.push(row);
+        }
+
+        let near_x = (tile_width / 2) as i32;
+        let near_y = (tile_height / 2) as i32;
+        let far_x = self.width as i32 - 1 - near_x;
+        let far_y = self.height as i32 - 1 - near_y;
+
+        let shipyards = if self.num_players == 2 {
+            vec![Position { x: near_x, y: near_y }, Position { x: far_x, y: near_y }]
+        } else {
+            vec![
+                Position { x: near_x, y: near_y }, Position { x: far_x, y: near_y },
+                Position { x: near_x, y: far_y }, Position { x: far_x, y: far_y },
+            ]
+        };
+
+        for shipyard in &shipyards {
+            halite[shipyard.y as usize][shipyard.x as usize] = 0;
+        }
+
+        let game_map = GameMap::new(self.width, self.height, &halite);
+        let players = shipyards.into_iter()
+            .enumerate()
+            .map(|(id, shipyard)| Player::new(PlayerId(id), shipyard))
+            .collect();
+
+        (game_map, players)
+    }
+
+    fn generate_tile(rng: &mut XorShiftRng, width: usize, height: usize) -> Vec<Vec<usize>> {
+        let source_noise: Vec<Vec<f64>> = (0..height)
+            .map(|_| (0..width).map(|_| rng.gen::<f64>()).collect())
+            .collect();
+
+        let max_octave = (width.min(height) as f64).log2().floor() as u32 + 1;
+        let mut region = vec![vec![0.0; width]; height];
+        for octave in 0..max_octave {
+            let wavelength = 1 << (max_octave - 1 - octave);
+            let smooth_noise = MapGenerator::smooth_noise(&source_noise, wavelength);
+            let weight = PERSISTENCE.powi(octave as i32);
+
+            for y in 0..height {
+                for x in 0..width {
+                    region[y][x] += smooth_noise[y][x] * weight;
+                }
+            }
+        }
+
+        let mut min_value = f64::MAX;
+        let mut max_value = f64::MIN;
+        for value in region.iter().flatten() {
+            min_value = min_value.min(*value);
+            max_value = max_value.max(*value);
+        }
+        let spread = if max_value > min_value { max_value - min_value } else { 1.0 };
+
+        let max_production = rng.gen_range(MIN_CELL_PRODUCTION, MAX_CELL_PRODUCTION + 1) as f64;
+        region.iter()
+            .map(|row| row.iter().map(|value| {
+                let normalized = (value - min_value) / spread;
+                let concentrated = normalized.powf(FACTOR_EXP_1);
+                (concentrated * max_production).round() as usize
+            }).collect())
+            .collect()
+    }
+
+    // Samples the source noise every `wavelength` cells and bilinearly interpolates between the
+    // samples, wrapping around the tile edges.
+    fn smooth_noise(source_noise: &[Vec<f64>], wavelength: usize) -> Vec<Vec<f64>> {
+        let height = source_noise.len();
+        let width = source_noise[0].len();
+        let mini_height = height.div_ceil(wavelength);
+        let mini_width = width.div_ceil(wavelength);
+
+        let mut smooth = vec![vec![0.0; width]; height];
+        for (y, smooth_row) in smooth.iter_mut().enumerate() {
+            let y0 = y / wavelength;
+            let y1 = (y0 + 1) % mini_height;
+            let y_fraction = (y % wavelength) as f64 / wavelength as f64;
+
+            for (x, smooth_value) in smooth_row.iter_mut().enumerate() {
+                let x0 = x / wavelength;
+                let x1 = (x0 + 1) % mini_width;
+                let x_fraction = (x % wavelength) as f64 / wavelength as f64;
+
+                let sample = |mini_x: usize, mini_y: usize| source_noise[mini_y * wavelength][mini_x * wavelength];
+                let top = sample(x0, y0) * (1.0 - x_fraction) + sample(x1, y0) * x_fraction;
+                let bottom = sample(x0, y1) * (1.0 - x_fraction) + sample(x1, y1) * x_fraction;
+                *smooth_value = top * (1.0 - y_fraction) + bottom * y_fraction;
+            }
+        }
+
+        smooth
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn halite(game_map: &GameMap) -> Vec<Vec<usize>> {
+        (0..game_map.height as i32)
+            .map(|y| (0..game_map.width as i32).map(|x| game_map.at_position(&Position { x, y }).halite).collect())
+            .collect()
+    }
+
+    #[test]
+    fn same_seed_generates_the_same_map() {
+        let (first, _) = MapGenerator::new(7, 40, 40, 2).generate();
+        let (second, _) = MapGenerator::new(7, 40, 40, 2).generate();
+        let (other, _) = MapGenerator::new(8, 40, 40, 2).generate();
+
+        assert_eq!(halite(&first), halite(&second));
+        assert_ne!(halite(&first), halite(&other));
+    }
+
+    #[test]
+    fn two_player_maps_mirror_left_and_right() {
+        let (game_map, players) = MapGenerator::new(11, 32, 48, 2).generate();
+        let halite = halite(&game_map);
+
+        for row in &halite {
+            for x in 0..32 {
+                assert_eq!(row[x], row[31 - x]);
+            }
+        }
+
+        let (left, right) = (players[0].shipyard.position, players[1].shipyard.position);
+        assert_eq!(left.y, right.y);
+        assert_eq!(left.x, 31 - right.x);
+    }
+
+    #[test]
+    fn four_player_maps_mirror_into_every_quadrant() {
+        let (game_map, players) = MapGenerator::new(3, 48, 48, 4).generate();
+        let halite = halite(&game_map);
+
+        for y in 0..48 {
+            for x in 0..48 {
+                assert_eq!(halite[y][x], halite[y][47 - x]);
+                assert_eq!(halite[y][x], halite[47 - y][x]);
+            }
+        }
+
+        let total_near = |shipyard: &Position| -> usize {
+            (-3..=3).flat_map(|dy| (-3..=3).map(move |dx| (dx, dy)))
+                .map(|(dx, dy)| halite[(shipyard.y + dy) as usize][(shipyard.x + dx) as usize])
+                .sum()
+        };
+        let around_first = total_near(&players[0].shipyard.position);
+        for player in &players {
+            assert_eq!(total_near(&player.shipyard.position), around_first);
+        }
+    }
+}
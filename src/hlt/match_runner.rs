@@ -0,0 +1,285 @@
+use hlt::command::Command;
+use hlt::constants::Constants;
+use hlt::map_generator::MapGenerator;
+use hlt::PlayerId;
+use hlt::replay::ReplayState;
+use hlt::replay::ReplayWriter;
+use hlt::simulator::Simulator;
+use std::io;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::Child;
+use std::process::ChildStdin;
+use std::process::Command as Process;
+use std::process::Stdio;
+use std::sync::mpsc;
+use std::sync::mpsc::Receiver;
+use std::sync::mpsc::RecvTimeoutError;
+use std::thread;
+use std::time::Duration;
+use std::time::Instant;
+
+pub struct MatchSettings {
+    pub seed: u64,
+    pub width: usize,
+    pub height: usize,
+    pub init_timeout: Duration,
+    pub turn_timeout: Duration,
+    pub replay_directory: Option<PathBuf>,
+}
+
+pub struct MatchResult {
+    pub names: Vec<String>,
+    pub rankings: Vec<PlayerId>,
+    pub halite: Vec<usize>,
+    pub timed_out: Vec<bool>,
+    pub errored: Vec<bool>,
+    pub turns: usize,
+}
+
+enum Reply {
+    Line(String),
+    TimedOut,
+    Disconnected,
+}
+
+// A bot launched through the shell, exactly like the engine launches the commands it is given.
+// Lines the bot prints are collected on a separate thread so reads can time out.
+struct BotProcess {
+    child: Child,
+    stdin: ChildStdin,
+    lines: Receiver<String>,
+}
+
+impl BotProcess {
+    fn launch(command: &str) -> io::Result<BotProcess> {
+        let mut process = if cfg!(windows) {
+            let mut process = Process::new("cmd");
+            process.arg("/C").arg(command);
+            process
+        } else {
+            let mut process = Process::new("sh");
+            process.arg("-c").arg(command);
+            process
+        };
+
+        let mut child = process.stdin(Stdio::piped()).stdout(Stdio::piped()).spawn()?;
+        let stdin = child.stdin.take().expect("Error: match runner: bot stdin should be piped.");
+        let stdout = child.stdout.take().expect("Error: match runner: bot stdout should be piped.");
+
+        let (sender, lines) = mpsc::channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                match line {
+                    Ok(line) => if sender.send(line).is_err() { break; },
+                    Err(_) => break,
+                }
+            }
+        });
+
+        Ok(BotProcess { child, stdin, lines })
+    }
+
+    fn send(&mut self, message: &str) -> bool {
+        self.stdin.write_all(message.as_bytes()).and_then(|_| self.stdin.flush()).is_ok()
+    }
+
+    fn receive(&self, deadline: Instant) -> Reply {
+        let timeout = deadline.saturating_duration_since(Instant::now());
+        match self.lines.recv_timeout(timeout) {
+            Ok(line) => Reply::Line(line),
+            Err(RecvTimeoutError::Timeout) => Reply::TimedOut,
+            Err(RecvTimeoutError::Disconnected) => Reply::Disconnected,
+        }
+    }
+}
+
+impl Drop for BotProcess {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+// Plays one game between the given bot commands on a generated map. Bots are seated in the order
+// given, so the first command plays as player 0. Bots that time out, crash or send invalid
+// commands are kicked from the game like the engine does.
+pub fn play_match(bot_commands: &[String], settings: &MatchSettings) -> io::Result<MatchResult> {
+    let num_players = bot_commands.len();
+    let (game_map, players) = MapGenerator::new(settings.seed, settings.width, settings.height, num_players).generate();
+    let mut simulator = Simulator::new(Constants::default_for_map(settings.width), game_map, players);
+
+    let mut bots = Vec::with_capacity(num_players);
+    for command in bot_commands {
+        bots.push(BotProcess::launch(command)?);
+    }
+
+    let mut names: Vec<String> = bot_commands.to_vec();
+    let mut timed_out = vec![false; num_players];
+    let mut errored = vec![false; num_players];
+
+    let deadline = Instant::now() + settings.init_timeout;
+    for (player_index, bot) in bots.iter_mut().enumerate() {
+        if !bot.send(&simulator.initial_message(PlayerId(player_index), settings.seed)) {
+            errored[player_index] = true;
+        }
+    }
+    for (player_index, bot) in bots.iter().enumerate() {
+        if errored[player_index] {
+            simulator.kick(PlayerId(player_index));
+            continue;
+        }
+
+        match bot.receive(deadline) {
+            Reply::Line(name) => names[player_index] = name.trim().to_string(),
+            Reply::TimedOut => {
+                timed_out[player_index] = true;
+                simulator.kick(PlayerId(player_index));
+            },
+            Reply::Disconnected => {
+                errored[player_index] = true;
+                simulator.kick(PlayerId(player_index));
+            },
+        }
+    }
+
+    let mut replay = match settings.replay_directory {
+        Some(_) => Some(ReplayWriter::new(&simulator.constants, settings.seed, &names, ReplayState::from(&simulator))),
+        None => None,
+    };
+
+    while !simulator.is_finished() {
+        let frame = simulator.frame_message();
+        let deadline = Instant::now() + settings.turn_timeout;
+        for (player_index, bot) in bots.iter_mut().enumerate() {
+            if !simulator.kicked[player_index] && !bot.send(&frame) {
+                errored[player_index] = true;
+            }
+        }
+
+        let mut commands: Vec<Vec<Command>> = (0..num_players).map(|_| Vec::new()).collect();
+        for (player_index, bot) in bots.iter().enumerate() {
+            if simulator.kicked[player_index] {
+                continue;
+            }
+            if errored[player_index] {
+                simulator.kick(PlayerId(player_index));
+                continue;
+            }
+
+            match bot.receive(deadline) {
+                Reply::Line(line) => match Command::parse_line(&line) {
+                    Some(player_commands) => commands[player_index] = player_commands,
+                    None => {
+                        errored[player_index] = true;
+                        simulator.kick(PlayerId(player_index));
+                    },
+                },
+                Reply::TimedOut => {
+                    timed_out[player_index] = true;
+                    simulator.kick(PlayerId(player_index));
+                },
+                Reply::Disconnected => {
+                    errored[player_index] = true;
+                    simulator.kick(PlayerId(player_index));
+                },
+            }
+        }
+
+        if let Some(replay) = &mut replay {
+            replay.record_turn(ReplayState::from(&simulator), &commands);
+        }
+
+        let already_kicked = simulator.kicked.clone();
+        simulator.process_turn(&commands);
+        for player_index in 0..num_players {
+            if simulator.kicked[player_index] && !already_kicked[player_index] {
+                errored[player_index] = true;
+            }
+        }
+    }
+
+    let rankings = simulator.rankings();
+    if let (Some(replay), Some(directory)) = (&mut replay, &settings.replay_directory) {
+        replay.finish(ReplayState::from(&simulator), &rankings);
+        let filename = format!("replay-{}-{}-{}.hlt", settings.seed, settings.width, settings.height);
+        replay.write(directory.join(filename))?;
+    }
+
+    Ok(MatchResult {
+        names,
+        rankings,
+        halite: simulator.players.iter().map(|player| player.halite).collect(),
+        timed_out,
+        errored,
+        turns: simulator.turn_number,
+    })
+}
+
+pub struct BotStatistics {
+    pub command: String,
+    pub games: usize,
+    pub wins: usize,
+    pub total_halite: usize,
+    pub timeouts: usize,
+    pub errors: usize,
+}
+
+impl BotStatistics {
+    pub fn win_rate(&self) -> f64 {
+        if self.games == 0 { 0.0 } else { self.wins as f64 / self.games as f64 }
+    }
+
+    pub fn average_halite(&self) -> f64 {
+        if self.games == 0 { 0.0 } else { self.total_halite as f64 / self.games as f64 }
+    }
+}
+
+// Accumulates results per bot over many games, whichever seat the bot played in.
+pub struct Tournament {
+    pub bots: Vec<BotStatistics>,
+}
+
+impl Tournament {
+    pub fn new(bot_commands: &[String]) -> Tournament {
+        let bots = bot_commands.iter()
+            .map(|command| BotStatistics { command: command.clone(), games: 0, wins: 0, total_halite: 0, timeouts: 0, errors: 0 })
+            .collect();
+
+        Tournament { bots }
+    }
+
+    // `seats` maps each player id of the game to the bot that played it.
+    pub fn record(&mut self, seats: &[usize], result: &MatchResult) {
+        for (player_index, bot_index) in seats.iter().enumerate() {
+            let bot = &mut self.bots[*bot_index];
+            bot.games += 1;
+            bot.total_halite += result.halite[player_index];
+            if result.rankings.first() == Some(&PlayerId(player_index)) {
+                bot.wins += 1;
+            }
+            if result.timed_out[player_index] {
+                bot.timeouts += 1;
+            }
+            if result.errored[player_index] {
+                bot.errors += 1;
+            }
+        }
+    }
+
+    pub fn report(&self) -> String {
+        let width = self.bots.iter().map(|bot| bot.command.len()).max().unwrap_or(0).max(3);
+        let mut report = format!("{:<width$}  {:>5}  {:>5}  {:>8}  {:>11}  {:>8}  {:>6}\n",
+            "Bot", "Games", "Wins", "Win rate", "Avg halite", "Timeouts", "Errors", width = width);
+
+        for bot in &self.bots {
+            report.push_str(&format!("{:<width$}  {:>5}  {:>5}  {:>7.1}%  {:>11.1}  {:>8}  {:>6}\n",
+                bot.command, bot.games, bot.wins, bot.win_rate() * 100.0, bot.average_halite(), bot.timeouts, bot.errors,
+                width = width));
+        }
+
+        report
+    }
+}
@@ -0,0 +1,202 @@
+use hlt::direction::Direction;
+use hlt::game::Game;
+use hlt::position::Position;
+use hlt::ship::Ship;
+use std::collections::VecDeque;
+
+#[derive(Copy, Clone, Debug)]
+pub struct MiningTarget {
+    pub position: Position,
+    // Expected halite delivered per turn spent going there, mining and returning, net of move costs.
+    pub value_per_turn: f64,
+    pub travel_turns: usize,
+    pub mining_turns: usize,
+    pub return_turns: usize,
+}
+
+// Rates the cells around a ship by how fast a trip to them turns into delivered halite. A trip is
+// the way there, a number of turns mining the cell while it shrinks by `1/extract_ratio` each turn,
+// and the way back to our nearest dropoff. Move costs are paid for every cell left on the way,
+// the cargo is capped at `max_halite`, and inspired cells yield their bonus.
+pub struct MiningPlanner {
+    // Halite spent on moves to bring a ship from each cell to our nearest dropoff, row-major.
+    return_costs: Vec<usize>,
+}
+
+impl MiningPlanner {
+    pub fn new(game: &Game) -> MiningPlanner {
+        let game_map = &game.game_map;
+        let width = game_map.width;
+        let num_cells = width * game_map.height;
+
+        let mut cells: Vec<(usize, Position)> = Vec::with_capacity(num_cells);
+        for y in 0..game_map.height {
+            for x in 0..width {
+                let position = Position { x: x as i32, y: y as i32 };
+                let distance = game_map.nearest_dropoff(&position, game.my_id).map_or(0, |nearest| nearest.distance);
+                cells.push((distance, position));
+            }
+        }
+        cells.sort_by_key(|&(distance, _)| distance);
+
+        // Cells closer to a dropoff are settled first, so each cell only has to look at the
+        // neighbours one step closer than itself.
+        let mut return_costs = vec![0; num_cells];
+        for &(distance, position) in &cells {
+            if distance == 0 {
+                continue;
+            }
+            let cheapest_next = position.get_surrounding_cardinals().iter()
+                .map(|neighbour| game_map.normalize(neighbour))
+                .filter(|neighbour| game_map.nearest_dropoff(neighbour, game.my_id).is_some_and(|nearest| nearest.distance + 1 == distance))
+                .map(|neighbour| return_costs[neighbour.y as usize * width + neighbour.x as usize])
+                .min()
+                .unwrap_or(0);
+            let inspired = game_map.is_inspired(&position, game.my_id);
+            let leave_cost = game.constants.move_cost(game_map.at_position(&position).halite, inspired);
+            return_costs[position.y as usize * width + position.x as usize] = leave_cost + cheapest_next;
+        }
+
+        MiningPlanner { return_costs }
+    }
+
+    // Every reachable cell within the search distance with a positive value, best first.
+    pub fn targets(&self, game: &Game, ship: &Ship) -> Vec<MiningTarget> {
+        let game_map = &game.game_map;
+        let constants = &game.constants;
+        let width = game_map.width;
+        let num_cells = width * game_map.height;
+
+        // Fewest turns first, then the cheapest way among the equally short ones.
+        let mut travel: Vec<Option<(usize, usize)>> = vec![None; num_cells];
+        let start = game_map.normalize(&ship.position);
+        travel[start.y as usize * width + start.x as usize] = Some((0, 0));
+        let mut queue = VecDeque::new();
+        queue.push_back(start);
+
+        let mut targets = Vec::new();
+        while let Some(position) = queue.pop_front() {
+            let index = position.y as usize * width + position.x as usize;
+            let (travel_turns, travel_cost) = travel[index].unwrap();
+
+            if let Some(target) = self.evaluate(game, ship, &position, travel_turns, travel_cost) {
+                targets.push(target);
+            }
+
+            // Cells further away are not considered, which keeps planning cheap on big maps.
+            if travel_turns == game.config.max_search_distance {
+                continue;
+            }
+            let inspired = game_map.is_inspired(&position, ship.owner);
+            let cost = travel_cost + constants.move_cost(game_map.at_position(&position).halite, inspired);
+            for direction in Direction::get_all_cardinals() {
+                let next = game_map.normalize(&position.directional_offset(direction));
+                let next_index = next.y as usize * width + next.x as usize;
+                match travel[next_index] {
+                    None => {
+                        travel[next_index] = Some((travel_turns + 1, cost));
+                        queue.push_back(next);
+                    },
+                    Some((turns, previous_cost)) if turns == travel_turns + 1 && cost < previous_cost => {
+                        travel[next_index] = Some((turns, cost));
+                    },
+                    _ => (),
+                }
+            }
+        }
+
+        targets.sort_by(|a, b| b.value_per_turn.partial_cmp(&a.value_per_turn).unwrap());
+        targets
+    }
+
+    pub fn best_target(&self, game: &Game, ship: &Ship) -> Option<MiningTarget> {
+        self.targets(game, ship).into_iter().next()
+    }
+
+    fn evaluate(&self, game: &Game, ship: &Ship, position: &Position, travel_turns: usize, travel_cost: usize) -> Option<MiningTarget> {
+        let game_map = &game.game_map;
+        let constants = &game.constants;
+        let inspired = game_map.is_inspired(position, game.my_id);
+        let return_turns = game_map.nearest_dropoff(position, game.my_id).map_or(0, |nearest| nearest.distance);
+
+        let initial_halite = game_map.at_position(position).halite;
+        let return_cost = self.return_costs[position.y as usize * game_map.width + position.x as usize];
+        let return_cost_after_cell = return_cost.saturating_sub(constants.move_cost(initial_halite, inspired));
+        let leave_cost = |cell_halite: usize| if return_turns == 0 { 0 } else { constants.move_cost(cell_halite, inspired) };
+
+        let mut cell_halite = initial_halite;
+        let mut cargo = ship.halite.saturating_sub(travel_cost);
+        let mut gained = 0;
+        let mut best: Option<MiningTarget> = None;
+
+        // Nobody sits on one cell for longer; the cell is worth little by then anyway.
+        for mining_turns in 1..game.config.max_mining_turns + 1 {
+            let (extracted, gain) = constants.extraction(cell_halite, cargo, inspired);
+            if gain == 0 {
+                break;
+            }
+            cell_halite -= extracted;
+            cargo += gain;
+            gained += gain;
+
+            let costs = travel_cost + return_cost_after_cell + leave_cost(cell_halite);
+            let turns = travel_turns + mining_turns + return_turns;
+            let value_per_turn = (gained as f64 - costs as f64) / turns as f64;
+            if value_per_turn > 0.0 && best.is_none_or(|best| value_per_turn > best.value_per_turn) {
+                best = Some(MiningTarget { position: *position, value_per_turn, travel_turns, mining_turns, return_turns });
+            }
+
+            if cargo >= constants.max_halite {
+                break;
+            }
+        }
+
+        best
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hlt::constants::Constants;
+    use hlt::game_map::GameMap;
+    use hlt::player::Player;
+    use hlt::PlayerId;
+    use hlt::simulator::Simulator;
+
+    #[test]
+    fn ranks_cells_by_halite_delivered_per_turn() {
+        // A 400 halite cell two moves from the shipyard and an 800 halite cell four moves away, on
+        // an otherwise empty map.
+        let mut halite = vec![vec![0; 32]; 32];
+        halite[16][10] = 400;
+        halite[12][8] = 800;
+        let players = vec![
+            Player::new(PlayerId(0), Position { x: 8, y: 16 }),
+            Player::new(PlayerId(1), Position { x: 24, y: 16 }),
+        ];
+        let mut simulator = Simulator::new(Constants::default_for_map(32), GameMap::new(32, 32, &halite), players);
+        let ship_id = simulator.add_ship(PlayerId(0), Position { x: 8, y: 16 }, 0);
+        let game = Game::from_simulator(&simulator, PlayerId(0));
+        let ship = &game.ships[&ship_id];
+
+        let targets = MiningPlanner::new(&game).targets(&game, ship);
+        assert_eq!(targets.len(), 2);
+
+        // Six turns take 200, 150, 113, 85, 63 and 48 from the far cell, 659 in all. Leaving the
+        // 141 left costs 14, and the trip takes 4 + 6 + 4 turns.
+        let far = targets[0];
+        assert_eq!(far.position, Position { x: 8, y: 12 });
+        assert_eq!((far.travel_turns, far.mining_turns, far.return_turns), (4, 6, 4));
+        assert!((far.value_per_turn - (659.0 - 14.0) / 14.0).abs() < 1e-9);
+
+        // Five turns take 100, 75, 57, 42 and 32 from the near cell, 306 in all. Leaving the 94
+        // left costs 9, and the trip takes 2 + 5 + 2 turns.
+        let near = targets[1];
+        assert_eq!(near.position, Position { x: 10, y: 16 });
+        assert_eq!((near.travel_turns, near.mining_turns, near.return_turns), (2, 5, 2));
+        assert!((near.value_per_turn - (306.0 - 9.0) / 9.0).abs() < 1e-9);
+
+        assert_eq!(MiningPlanner::new(&game).best_target(&game, ship).map(|target| target.position), Some(far.position));
+    }
+}
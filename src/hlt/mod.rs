@@ -1,38 +1,73 @@
 #[allow(dead_code)]
+pub mod assignment;
+#[allow(dead_code)]
+pub mod clock;
+#[allow(dead_code)]
 pub mod command;
 #[allow(dead_code)]
+pub mod config;
+#[allow(dead_code)]
 pub mod constants;
 #[allow(dead_code)]
 pub mod direction;
 #[allow(dead_code)]
 pub mod dropoff;
 #[allow(dead_code)]
+pub mod dropoff_policy;
+#[allow(dead_code)]
 pub mod entity;
 #[allow(dead_code)]
+pub mod fleet;
+#[allow(dead_code)]
 pub mod game;
 #[allow(dead_code)]
 pub mod game_map;
 #[allow(dead_code)]
+pub mod history;
+#[allow(dead_code)]
 pub mod log;
 #[allow(dead_code)]
 pub mod map_cell;
 #[allow(dead_code)]
+pub mod map_generator;
+#[allow(dead_code)]
+pub mod match_runner;
+#[allow(dead_code)]
+pub mod mining;
+#[allow(dead_code)]
+pub mod move_resolver;
+#[allow(dead_code)]
 pub mod player;
 #[allow(dead_code)]
 pub mod position;
 #[allow(dead_code)]
+pub mod recording;
+#[allow(dead_code)]
+pub mod replay;
+#[allow(dead_code)]
+pub mod return_scheduler;
+#[allow(dead_code)]
 pub mod ship;
 #[allow(dead_code)]
 pub mod shipyard;
+#[allow(dead_code)]
+pub mod simulator;
+#[allow(dead_code)]
+pub mod spawn_advisor;
+
+#[allow(dead_code)]
+pub mod transport;
+#[allow(dead_code)]
+pub mod tuning;
 
 #[allow(dead_code)]
 mod input;
 
-#[derive(Copy, Clone, Eq, PartialEq, Hash)]
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
 pub struct PlayerId(pub usize);
 
-#[derive(Copy, Clone, Eq, PartialEq, Hash)]
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
 pub struct DropoffId(pub usize);
 
-#[derive(Copy, Clone, Eq, PartialEq, Hash)]
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
 pub struct ShipId(pub usize);
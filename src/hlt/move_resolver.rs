@@ -0,0 +1,368 @@
+use hlt::command::Command;
+use hlt::constants::Constants;
+use hlt::direction::Direction;
+use hlt::game_map::GameMap;
+use hlt::game_map::RiskPolicy;
+use hlt::PlayerId;
+use hlt::position::Position;
+use hlt::ship::Ship;
+use hlt::ShipId;
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::collections::HashMap;
+use std::collections::HashSet;
+
+struct MoveRequest {
+    ship_id: ShipId,
+    owner: PlayerId,
+    position: Position,
+    halite: usize,
+    directions: Vec<Direction>,
+    priority: usize,
+}
+
+pub struct ResolvedMove {
+    pub ship_id: ShipId,
+    pub direction: Direction,
+    pub destination: Position,
+}
+
+impl ResolvedMove {
+    pub fn command(&self) -> Command {
+        Command::move_ship(self.ship_id, self.direction)
+    }
+}
+
+// Turns the moves every ship would like to make into one set of moves where no two of our ships
+// end the turn on the same cell. Ships list the directions they want in order of preference and
+// staying still is always the last resort. Moving into a cell another ship is leaving is allowed,
+// so swaps and chains of ships following each other resolve without anyone standing still.
+//
+// The assignment with the lowest total cost is picked, where taking a ship's n-th choice costs
+// n times its priority, so ships with a higher priority get their way first.
+//
+// Moves onto cells enemies can reach next turn are dropped or kept according to the risk policy,
+// which under `RiskPolicy::Collide` also lets ships move onto enemy ships.
+pub struct MoveResolver {
+    requests: Vec<MoveRequest>,
+    blocked: HashSet<Position>,
+    collision_sites: HashSet<Position>,
+    risk_policy: RiskPolicy,
+}
+
+impl MoveResolver {
+    pub fn new(risk_policy: RiskPolicy) -> MoveResolver {
+        MoveResolver { requests: Vec::new(), blocked: HashSet::new(), collision_sites: HashSet::new(), risk_policy }
+    }
+
+    // Directions are in order of preference. Priority should be at least 1.
+    pub fn request(&mut self, ship: &Ship, directions: &[Direction], priority: usize) {
+        self.requests.push(MoveRequest {
+            ship_id: ship.id,
+            owner: ship.owner,
+            position: ship.position,
+            halite: ship.halite,
+            directions: directions.to_vec(),
+            priority: priority.max(1),
+        });
+    }
+
+    // Keeps ships from moving onto a cell. Ships already on it may still stay there.
+    pub fn block(&mut self, game_map: &GameMap, position: &Position) {
+        self.blocked.insert(game_map.normalize(position));
+    }
+
+    // Lets any number of our ships end the turn on a cell, e.g. our own shipyard or dropoffs at the
+    // end of the game where crashing into each other still delivers all the cargo.
+    pub fn allow_collisions_at(&mut self, game_map: &GameMap, position: &Position) {
+        self.collision_sites.insert(game_map.normalize(position));
+    }
+
+    // Cells holding a ship that isn't part of the requests are treated as blocked, as are moves
+    // the ship can't pay for and risky moves.
+    pub fn resolve(&self, game_map: &GameMap, constants: &Constants) -> Vec<ResolvedMove> {
+        let requested: HashSet<ShipId> = self.requests.iter().map(|request| request.ship_id).collect();
+
+        let mut cells: Vec<Position> = Vec::new();
+        let mut cell_indices: HashMap<Position, usize> = HashMap::new();
+        let mut choices: Vec<Vec<(Direction, usize, i64)>> = Vec::with_capacity(self.requests.len());
+
+        for request in &self.requests {
+            let cell_halite = game_map.at_position(&request.position).halite;
+            let inspired = game_map.is_inspired(&request.position, request.owner);
+            let can_move = request.halite >= constants.move_cost(cell_halite, inspired);
+
+            let mut ship_choices = Vec::new();
+            let mut rank = 0;
+            for direction in request.directions.iter().chain([Direction::Still].iter()) {
+                if *direction != Direction::Still && !can_move {
+                    continue;
+                }
+
+                let destination = game_map.normalize(&request.position.directional_offset(*direction));
+                if *direction != Direction::Still && !self.collision_sites.contains(&destination) {
+                    if self.blocked.contains(&destination) || game_map.is_risky(&destination, request.halite, self.risk_policy) {
+                        continue;
+                    }
+                    let ramming = self.risk_policy == RiskPolicy::Collide &&
+                        game_map.threat(&destination).is_some_and(|threat| threat.occupied);
+                    match game_map.at_position(&destination).ship {
+                        Some(ship_id) if !requested.contains(&ship_id) && !ramming => continue,
+                        _ => (),
+                    }
+                }
+                if ship_choices.iter().any(|&(chosen, _, _)| chosen == *direction) {
+                    continue;
+                }
+
+                let next_index = cells.len();
+                let cell_index = *cell_indices.entry(destination).or_insert(next_index);
+                if cell_index == next_index {
+                    cells.push(destination);
+                }
+
+                ship_choices.push((*direction, cell_index, (rank * request.priority) as i64));
+                rank += 1;
+            }
+            choices.push(ship_choices);
+        }
+
+        // Source, one node per ship, one node per cell, sink.
+        let num_ships = self.requests.len();
+        let source = 0;
+        let sink = 1 + num_ships + cells.len();
+        let mut flow = MinCostFlow::new(sink + 1);
+
+        let mut choice_edges: Vec<Vec<usize>> = Vec::with_capacity(num_ships);
+        for (ship_index, ship_choices) in choices.iter().enumerate() {
+            flow.add_edge(source, 1 + ship_index, 1, 0);
+            let edges = ship_choices.iter()
+                .map(|&(_, cell_index, cost)| flow.add_edge(1 + ship_index, 1 + num_ships + cell_index, 1, cost))
+                .collect();
+            choice_edges.push(edges);
+        }
+        for (cell_index, cell) in cells.iter().enumerate() {
+            let capacity = if self.collision_sites.contains(cell) { num_ships as i64 } else { 1 };
+            flow.add_edge(1 + num_ships + cell_index, sink, capacity, 0);
+        }
+
+        flow.run(source, sink);
+
+        let mut moves = Vec::with_capacity(num_ships);
+        for (ship_index, request) in self.requests.iter().enumerate() {
+            // Staying still is always possible since no two of our ships start on the same cell.
+            let direction = choices[ship_index].iter().zip(&choice_edges[ship_index])
+                .find(|&(_, edge)| flow.is_used(*edge))
+                .map(|(&(direction, _, _), _)| direction)
+                .unwrap_or(Direction::Still);
+
+            moves.push(ResolvedMove {
+                ship_id: request.ship_id,
+                direction,
+                destination: game_map.normalize(&request.position.directional_offset(direction)),
+            });
+        }
+
+        moves
+    }
+}
+
+struct Edge {
+    to: usize,
+    capacity: i64,
+    cost: i64,
+}
+
+// Min cost max flow by successive shortest paths, using Dijkstra with potentials. Edges are
+// stored in pairs so an edge's reverse is at index ^ 1.
+struct MinCostFlow {
+    edges: Vec<Edge>,
+    adjacency: Vec<Vec<usize>>,
+}
+
+#[derive(Eq, PartialEq)]
+struct QueueEntry {
+    distance: i64,
+    node: usize,
+}
+
+impl Ord for QueueEntry {
+    fn cmp(&self, other: &QueueEntry) -> Ordering {
+        other.distance.cmp(&self.distance)
+            .then_with(|| other.node.cmp(&self.node))
+    }
+}
+
+impl PartialOrd for QueueEntry {
+    fn partial_cmp(&self, other: &QueueEntry) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl MinCostFlow {
+    fn new(num_nodes: usize) -> MinCostFlow {
+        MinCostFlow { edges: Vec::new(), adjacency: (0..num_nodes).map(|_| Vec::new()).collect() }
+    }
+
+    fn add_edge(&mut self, from: usize, to: usize, capacity: i64, cost: i64) -> usize {
+        let index = self.edges.len();
+        self.edges.push(Edge { to, capacity, cost });
+        self.edges.push(Edge { to: from, capacity: 0, cost: -cost });
+        self.adjacency[from].push(index);
+        self.adjacency[to].push(index + 1);
+        index
+    }
+
+    fn is_used(&self, edge: usize) -> bool {
+        self.edges[edge ^ 1].capacity > 0
+    }
+
+    // All costs start out non-negative, so zero potentials are valid for the first search.
+    fn run(&mut self, source: usize, sink: usize) {
+        let num_nodes = self.adjacency.len();
+        let mut potential = vec![0i64; num_nodes];
+
+        loop {
+            let mut distance = vec![i64::MAX; num_nodes];
+            let mut previous_edge: Vec<Option<usize>> = vec![None; num_nodes];
+            let mut queue = BinaryHeap::new();
+            distance[source] = 0;
+            queue.push(QueueEntry { distance: 0, node: source });
+
+            while let Some(QueueEntry { distance: node_distance, node }) = queue.pop() {
+                if node_distance > distance[node] {
+                    continue;
+                }
+                for &edge_index in &self.adjacency[node] {
+                    let edge = &self.edges[edge_index];
+                    if edge.capacity <= 0 {
+                        continue;
+                    }
+                    let next_distance = node_distance + edge.cost + potential[node] - potential[edge.to];
+                    if next_distance < distance[edge.to] {
+                        distance[edge.to] = next_distance;
+                        previous_edge[edge.to] = Some(edge_index);
+                        queue.push(QueueEntry { distance: next_distance, node: edge.to });
+                    }
+                }
+            }
+
+            if distance[sink] == i64::MAX {
+                break;
+            }
+            for node in 0..num_nodes {
+                if distance[node] != i64::MAX {
+                    potential[node] += distance[node];
+                }
+            }
+
+            let mut amount = i64::MAX;
+            let mut node = sink;
+            while let Some(edge_index) = previous_edge[node] {
+                amount = amount.min(self.edges[edge_index].capacity);
+                node = self.edges[edge_index ^ 1].to;
+            }
+
+            let mut node = sink;
+            while let Some(edge_index) = previous_edge[node] {
+                self.edges[edge_index].capacity -= amount;
+                self.edges[edge_index ^ 1].capacity += amount;
+                node = self.edges[edge_index ^ 1].to;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn empty_map(cell_halite: usize) -> GameMap {
+        GameMap::new(16, 16, &vec![vec![cell_halite; 16]; 16])
+    }
+
+    fn ship(game_map: &mut GameMap, id: usize, x: i32, y: i32, halite: usize) -> Ship {
+        let position = Position { x, y };
+        game_map.at_position_mut(&position).mark_unsafe(ShipId(id));
+        Ship::new(PlayerId(0), ShipId(id), position, halite, 1000)
+    }
+
+    fn directions(moves: &[ResolvedMove]) -> Vec<(usize, Direction)> {
+        let mut directions: Vec<(usize, Direction)> = moves.iter().map(|resolved| (resolved.ship_id.0, resolved.direction)).collect();
+        directions.sort_by_key(|&(ship_id, _)| ship_id);
+        directions
+    }
+
+    #[test]
+    fn ships_swap_places() {
+        let mut game_map = empty_map(0);
+        let a = ship(&mut game_map, 0, 2, 2, 0);
+        let b = ship(&mut game_map, 1, 3, 2, 0);
+
+        let mut resolver = MoveResolver::new(RiskPolicy::Avoid);
+        resolver.request(&a, &[Direction::East], 1);
+        resolver.request(&b, &[Direction::West], 1);
+
+        let moves = resolver.resolve(&game_map, &Constants::default_for_map(16));
+        assert_eq!(directions(&moves), vec![(0, Direction::East), (1, Direction::West)]);
+    }
+
+    #[test]
+    fn chain_of_ships_follows_its_leader() {
+        let mut game_map = empty_map(0);
+        let ships: Vec<Ship> = (0..3).map(|id| ship(&mut game_map, id, 2 + id as i32, 2, 0)).collect();
+
+        let mut resolver = MoveResolver::new(RiskPolicy::Avoid);
+        for ship in &ships {
+            resolver.request(ship, &[Direction::East], 1);
+        }
+
+        let moves = resolver.resolve(&game_map, &Constants::default_for_map(16));
+        assert_eq!(directions(&moves), vec![(0, Direction::East), (1, Direction::East), (2, Direction::East)]);
+        let leader = moves.iter().find(|resolved| resolved.ship_id == ShipId(2));
+        assert_eq!(leader.map(|resolved| resolved.destination), Some(Position { x: 5, y: 2 }));
+    }
+
+    #[test]
+    fn chain_stops_behind_a_ship_that_is_not_moving() {
+        let mut game_map = empty_map(0);
+        let ships: Vec<Ship> = (0..3).map(|id| ship(&mut game_map, id, 2 + id as i32, 2, 0)).collect();
+        ship(&mut game_map, 9, 5, 2, 0);
+
+        let mut resolver = MoveResolver::new(RiskPolicy::Avoid);
+        for ship in &ships {
+            resolver.request(ship, &[Direction::East], 1);
+        }
+
+        let moves = resolver.resolve(&game_map, &Constants::default_for_map(16));
+        assert_eq!(directions(&moves), vec![(0, Direction::Still), (1, Direction::Still), (2, Direction::Still)]);
+    }
+
+    #[test]
+    fn higher_priority_ship_gets_the_contested_cell() {
+        let mut game_map = empty_map(0);
+        let low = ship(&mut game_map, 0, 2, 2, 0);
+        let high = ship(&mut game_map, 1, 4, 2, 0);
+
+        let mut resolver = MoveResolver::new(RiskPolicy::Avoid);
+        resolver.request(&low, &[Direction::East, Direction::North], 1);
+        resolver.request(&high, &[Direction::West, Direction::North], 5);
+
+        let moves = resolver.resolve(&game_map, &Constants::default_for_map(16));
+        assert_eq!(directions(&moves), vec![(0, Direction::North), (1, Direction::West)]);
+    }
+
+    #[test]
+    fn ship_that_cannot_pay_stays_and_the_ship_behind_it_waits() {
+        let mut game_map = empty_map(100);
+        let stuck = ship(&mut game_map, 0, 3, 2, 5);
+        let behind = ship(&mut game_map, 1, 2, 2, 500);
+
+        let mut resolver = MoveResolver::new(RiskPolicy::Avoid);
+        resolver.request(&stuck, &[Direction::East], 1);
+        resolver.request(&behind, &[Direction::East], 1);
+
+        let moves = resolver.resolve(&game_map, &Constants::default_for_map(16));
+        assert_eq!(directions(&moves), vec![(0, Direction::Still), (1, Direction::Still)]);
+    }
+}
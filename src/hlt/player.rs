@@ -17,6 +17,7 @@ pub struct Player {
 }
 
 impl Player {
+    #[allow(clippy::too_many_arguments)]
     pub fn update(
         &mut self,
         input: &mut Input,
@@ -44,6 +45,12 @@ impl Player {
         }
     }
 
+    pub fn new(id: PlayerId, shipyard_position: Position) -> Player {
+        let shipyard = Shipyard { owner: id, position: shipyard_position };
+
+        Player { id, shipyard, halite: 0, ship_ids: Vec::new(), dropoff_ids: Vec::new() }
+    }
+
     pub fn generate(input: &mut Input) -> Player {
         input.read_and_parse_line();
         let id = PlayerId(input.next_usize());
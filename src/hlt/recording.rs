@@ -0,0 +1,111 @@
+use serde_json;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fs;
+
+// Lines starting with this are the bot's own notes rather than lines from the engine, which never
+// sends one.
+const NOTE_PREFIX: &str = "# ";
+
+// A game recorded with --record: every line the engine sent, plus notes on what else went into the
+// bot's decisions, so a replay can make the same ones. The notes are the seed and the strategy
+// config, written before the first turn, and for every turn that ran out of time the budget polls
+// that found it exhausted.
+pub struct Recording {
+    pub engine_lines: Vec<String>,
+    pub seed: Option<u64>,
+    pub config: Option<Value>,
+    pub exhausted_polls: HashMap<usize, Vec<usize>>,
+}
+
+impl Recording {
+    pub fn open(path: &str) -> Result<Recording, String> {
+        let contents = fs::read_to_string(path)
+            .map_err(|e| format!("Error: recording: could not read {}: {}", path, e))?;
+        Recording::parse(&contents)
+    }
+
+    pub fn parse(contents: &str) -> Result<Recording, String> {
+        let mut recording = Recording { engine_lines: Vec::new(), seed: None, config: None, exhausted_polls: HashMap::new() };
+
+        for line in contents.lines() {
+            let note = match line.strip_prefix(NOTE_PREFIX) {
+                Some(note) => note,
+                None => {
+                    recording.engine_lines.push(line.to_string());
+                    continue;
+                },
+            };
+
+            let (kind, rest) = match note.find(' ') {
+                Some(index) => (&note[..index], note[index + 1..].trim()),
+                None => (note, ""),
+            };
+            match kind {
+                "seed" => {
+                    let seed = rest.parse().map_err(|_| format!("Error: recording: bad seed '{}'.", rest))?;
+                    recording.seed = Some(seed);
+                },
+                "config" => {
+                    let config = serde_json::from_str(rest).map_err(|e| format!("Error: recording: bad config: {}", e))?;
+                    recording.config = Some(config);
+                },
+                "exhausted" => {
+                    let numbers: Result<Vec<usize>, _> = rest.split_whitespace().map(|number| number.parse()).collect();
+                    let numbers = numbers.map_err(|_| format!("Error: recording: bad exhausted polls '{}'.", rest))?;
+                    match numbers.split_first() {
+                        Some((turn_number, polls)) => { recording.exhausted_polls.insert(*turn_number, polls.to_vec()); },
+                        None => return Err("Error: recording: exhausted polls without a turn number.".to_string()),
+                    }
+                },
+                _ => return Err(format!("Error: recording: unknown note '{}'.", line)),
+            }
+        }
+
+        Ok(recording)
+    }
+
+    pub fn seed_note(seed: u64) -> String {
+        format!("{}seed {}", NOTE_PREFIX, seed)
+    }
+
+    pub fn config_note(config: &Value) -> String {
+        format!("{}config {}", NOTE_PREFIX, config)
+    }
+
+    pub fn exhausted_polls_note(turn_number: usize, polls: &[usize]) -> String {
+        let polls: Vec<String> = polls.iter().map(|poll| poll.to_string()).collect();
+        format!("{}exhausted {} {}", NOTE_PREFIX, turn_number, polls.join(" "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn notes_are_parsed_apart_from_the_engine_lines() {
+        let contents = [
+            "{\"MAX_TURNS\":400}".to_string(),
+            "2 0".to_string(),
+            Recording::seed_note(42),
+            Recording::config_note(&json!({ "return_margin": 250 })),
+            "1".to_string(),
+            Recording::exhausted_polls_note(1, &[3, 4]),
+        ].join("\n");
+
+        let recording = Recording::parse(&contents).expect("the recording should parse");
+        assert_eq!(recording.engine_lines, vec!["{\"MAX_TURNS\":400}", "2 0", "1"]);
+        assert_eq!(recording.seed, Some(42));
+        assert_eq!(recording.config, Some(json!({ "return_margin": 250 })));
+        assert_eq!(recording.exhausted_polls.get(&1), Some(&vec![3, 4]));
+    }
+
+    #[test]
+    fn unknown_or_malformed_notes_are_rejected() {
+        assert!(Recording::parse("# weather sunny").is_err());
+        assert!(Recording::parse("# seed soon").is_err());
+        assert!(Recording::parse("# exhausted").is_err());
+    }
+}
@@ -0,0 +1,785 @@
+use hlt::command::Command;
+use hlt::constants::Constants;
+use hlt::direction::Direction;
+use hlt::dropoff::Dropoff;
+use hlt::DropoffId;
+use hlt::game::Game;
+use hlt::game_map::GameMap;
+use hlt::map_cell::Structure;
+use hlt::player::Player;
+use hlt::PlayerId;
+use hlt::position::Position;
+use hlt::ship::Ship;
+use hlt::ShipId;
+use hlt::simulator::Simulator;
+use serde_json;
+use serde_json::json;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::fs::File;
+use std::io;
+use std::io::Read;
+use std::io::Write;
+use std::path::Path;
+use zstd;
+
+struct ReplayShip {
+    owner: PlayerId,
+    id: ShipId,
+    position: Position,
+    halite: usize,
+}
+
+// One entry of the replay's full_frames. Entities are the state at the start of the turn, while
+// cells, constructions and halite are the changes the turn made.
+struct Frame {
+    ships: Vec<ReplayShip>,
+    cells: Vec<(Position, usize)>,
+    constructs: Vec<Dropoff>,
+    halite: Vec<usize>,
+    commands: Vec<Vec<Command>>,
+}
+
+// The state every bot saw at the start of a turn, and the commands each of them sent back.
+pub struct ReplayTurn {
+    pub turn_number: usize,
+    pub game_map: GameMap,
+    pub players: Vec<Player>,
+    pub ships: HashMap<ShipId, Ship>,
+    pub dropoffs: HashMap<DropoffId, Dropoff>,
+    pub commands: Vec<Vec<Command>>,
+}
+
+// A game read from a .hlt replay file, the zstd-compressed JSON the engine writes with
+// --replay-directory.
+pub struct Replay {
+    pub constants: Constants,
+    pub map_seed: u64,
+    pub player_names: Vec<String>,
+    pub width: usize,
+    pub height: usize,
+    pub num_turns: usize,
+    initial_halite: Vec<Vec<usize>>,
+    shipyards: Vec<Position>,
+    frames: Vec<Frame>,
+}
+
+impl Replay {
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Replay> {
+        Replay::read(File::open(path)?)
+    }
+
+    pub fn read<R: Read>(reader: R) -> io::Result<Replay> {
+        let mut json = String::new();
+        zstd::Decoder::new(reader)?.read_to_string(&mut json)?;
+        Replay::parse(&json)
+    }
+
+    pub fn parse(json: &str) -> io::Result<Replay> {
+        let root: Value = serde_json::from_str(json).map_err(|e| invalid(&format!("not valid JSON: {}", e)))?;
+
+        let constants = Constants::parse(&field(&root, "GAME_CONSTANTS")?.to_string()).map_err(|e| invalid(&e))?;
+        let map_seed = field(&root, "map_generator_seed")?.as_u64().ok_or_else(|| invalid("map_generator_seed is not a number"))?;
+
+        let mut player_names = Vec::new();
+        let mut shipyards = Vec::new();
+        for player in array(field(&root, "players")?)? {
+            let id = number(field(player, "player_id")?)?;
+            if id != player_names.len() {
+                return Err(invalid("players are not listed in id order"));
+            }
+
+            player_names.push(field(player, "name")?.as_str().unwrap_or("").to_string());
+            shipyards.push(position(field(player, "factory_location")?)?);
+        }
+
+        let production_map = field(&root, "production_map")?;
+        let width = number(field(production_map, "width")?)?;
+        let height = number(field(production_map, "height")?)?;
+        let mut initial_halite = Vec::with_capacity(height);
+        for row in array(field(production_map, "grid")?)? {
+            let mut halite_row = Vec::with_capacity(width);
+            for cell in array(row)? {
+                halite_row.push(number(field(cell, "energy")?)?);
+            }
+            if halite_row.len() != width {
+                return Err(invalid("production map row does not match the map width"));
+            }
+            initial_halite.push(halite_row);
+        }
+        if initial_halite.len() != height {
+            return Err(invalid("production map does not match the map height"));
+        }
+        for shipyard in &shipyards {
+            on_map(*shipyard, width, height)?;
+        }
+
+        let mut frames = Vec::new();
+        for frame in array(field(&root, "full_frames")?)? {
+            frames.push(Replay::parse_frame(frame, width, height, player_names.len())?);
+        }
+        if frames.is_empty() {
+            return Err(invalid("replay has no frames"));
+        }
+
+        let recorded_turns = root.get("game_statistics")
+            .and_then(|statistics| statistics.get("number_turns"))
+            .and_then(|turns| turns.as_u64())
+            .map(|turns| turns as usize)
+            .unwrap_or(frames.len() - 1);
+        let num_turns = recorded_turns.min(frames.len() - 1);
+
+        Ok(Replay { constants, map_seed, player_names, width, height, num_turns, initial_halite, shipyards, frames })
+    }
+
+    // Reconstructs the start of a turn, numbered from 1 like the turns the engine sends.
+    pub fn turn(&self, turn_number: usize) -> Option<ReplayTurn> {
+        if turn_number == 0 {
+            return None;
+        }
+        self.turns().nth(turn_number - 1)
+    }
+
+    pub fn turns(&self) -> ReplayTurns<'_> {
+        ReplayTurns {
+            replay: self,
+            turn_number: 0,
+            halite: self.initial_halite.clone(),
+            dropoffs: Vec::new(),
+        }
+    }
+
+    // Positions and owners are checked against the map and the player list, so turns can be rebuilt
+    // from any frame that parses.
+    fn parse_frame(frame: &Value, width: usize, height: usize, num_players: usize) -> io::Result<Frame> {
+        let mut ships = Vec::new();
+        if let Some(entities) = frame.get("entities").and_then(|entities| entities.as_object()) {
+            for (owner, player_ships) in entities {
+                let owner = parse_key(owner)?;
+                if owner >= num_players {
+                    return Err(invalid("entities for an unknown player"));
+                }
+                for (id, ship) in player_ships.as_object().ok_or_else(|| invalid("entities is not an object"))? {
+                    let id = ShipId(parse_key(id)?);
+                    let position = on_map(position(ship)?, width, height)?;
+                    ships.push(ReplayShip { owner: PlayerId(owner), id, position, halite: number(field(ship, "energy")?)? });
+                }
+            }
+        }
+        ships.sort_by_key(|ship| ship.id.0);
+
+        let mut cells = Vec::new();
+        if let Some(changed) = frame.get("cells") {
+            for cell in array(changed)? {
+                cells.push((on_map(position(cell)?, width, height)?, number(field(cell, "production")?)?));
+            }
+        }
+
+        let mut constructs = Vec::new();
+        if let Some(events) = frame.get("events") {
+            for event in array(events)? {
+                if field(event, "type")?.as_str() == Some("construct") {
+                    let owner = number(field(event, "owner_id")?)?;
+                    if owner >= num_players {
+                        return Err(invalid("construct event for an unknown player"));
+                    }
+                    constructs.push(Dropoff {
+                        owner: PlayerId(owner),
+                        id: DropoffId(number(field(event, "id")?)?),
+                        position: on_map(position(field(event, "location")?)?, width, height)?,
+                    });
+                }
+            }
+        }
+
+        let mut halite = vec![0; num_players];
+        if let Some(energy) = frame.get("energy").and_then(|energy| energy.as_object()) {
+            for (owner, amount) in energy {
+                let owner = parse_key(owner)?;
+                if owner < num_players {
+                    halite[owner] = number(amount)?;
+                }
+            }
+        }
+
+        let mut commands: Vec<Vec<Command>> = (0..num_players).map(|_| Vec::new()).collect();
+        if let Some(moves) = frame.get("moves").and_then(|moves| moves.as_object()) {
+            for (owner, player_moves) in moves {
+                let owner = parse_key(owner)?;
+                if owner >= num_players {
+                    return Err(invalid("moves for an unknown player"));
+                }
+                for player_move in array(player_moves)? {
+                    commands[owner].push(Replay::parse_command(player_move)?);
+                }
+            }
+        }
+
+        Ok(Frame { ships, cells, constructs, halite, commands })
+    }
+
+    fn parse_command(command: &Value) -> io::Result<Command> {
+        match field(command, "type")?.as_str() {
+            Some("g") => Ok(Command::spawn_ship()),
+            Some("c") => Ok(Command::transform_ship_into_dropoff_site(ShipId(number(field(command, "id")?)?))),
+            Some("m") => {
+                let ship_id = ShipId(number(field(command, "id")?)?);
+                let direction = field(command, "direction")?.as_str()
+                    .and_then(|direction| direction.chars().next())
+                    .and_then(Direction::from_char_encoding)
+                    .ok_or_else(|| invalid("move has an unknown direction"))?;
+                Ok(Command::move_ship(ship_id, direction))
+            },
+            _ => Err(invalid("unknown command type")),
+        }
+    }
+}
+
+// Walks through the turns of a replay, applying each frame's changes to the map as it goes.
+pub struct ReplayTurns<'a> {
+    replay: &'a Replay,
+    turn_number: usize,
+    halite: Vec<Vec<usize>>,
+    dropoffs: Vec<Dropoff>,
+}
+
+impl<'a> Iterator for ReplayTurns<'a> {
+    type Item = ReplayTurn;
+
+    fn next(&mut self) -> Option<ReplayTurn> {
+        let replay = self.replay;
+        if self.turn_number >= replay.num_turns {
+            return None;
+        }
+
+        if self.turn_number > 0 {
+            let previous = &replay.frames[self.turn_number];
+            for (position, halite) in &previous.cells {
+                self.halite[position.y as usize][position.x as usize] = *halite;
+            }
+            for dropoff in &previous.constructs {
+                self.dropoffs.push(dropoff.clone());
+            }
+        }
+
+        let bank = &replay.frames[self.turn_number].halite;
+        self.turn_number += 1;
+        let frame = &replay.frames[self.turn_number];
+
+        let mut game_map = GameMap::new(replay.width, replay.height, &self.halite);
+        let mut players: Vec<Player> = replay.shipyards.iter()
+            .enumerate()
+            .map(|(id, shipyard)| Player::new(PlayerId(id), *shipyard))
+            .collect();
+
+        let mut ships = HashMap::new();
+        for ship in &frame.ships {
+            players[ship.owner.0].ship_ids.push(ship.id);
+            game_map.at_position_mut(&ship.position).mark_unsafe(ship.id);
+            ships.insert(ship.id, Ship::new(ship.owner, ship.id, ship.position, ship.halite, replay.constants.max_halite));
+        }
+
+        let mut dropoffs = HashMap::new();
+        for dropoff in &self.dropoffs {
+            players[dropoff.owner.0].dropoff_ids.push(dropoff.id);
+            game_map.at_position_mut(&dropoff.position).structure = Structure::Dropoff(dropoff.id);
+            dropoffs.insert(dropoff.id, dropoff.clone());
+        }
+
+        for player in &mut players {
+            player.halite = bank[player.id.0];
+            game_map.at_position_mut(&player.shipyard.position).structure = Structure::Shipyard(player.id);
+        }
+
+        let commands = frame.commands.clone();
+
+        Some(ReplayTurn { turn_number: self.turn_number, game_map, players, ships, dropoffs, commands })
+    }
+}
+
+// The part of a game a replay records each turn, taken from a bot's `Game` or from the simulator.
+pub struct ReplayState<'a> {
+    pub game_map: &'a GameMap,
+    pub players: &'a [Player],
+    pub ships: &'a HashMap<ShipId, Ship>,
+    pub dropoffs: &'a HashMap<DropoffId, Dropoff>,
+}
+
+impl<'a> From<&'a Game> for ReplayState<'a> {
+    fn from(game: &'a Game) -> ReplayState<'a> {
+        ReplayState { game_map: &game.game_map, players: &game.players, ships: &game.ships, dropoffs: &game.dropoffs }
+    }
+}
+
+impl<'a> From<&'a Simulator> for ReplayState<'a> {
+    fn from(simulator: &'a Simulator) -> ReplayState<'a> {
+        ReplayState {
+            game_map: &simulator.game_map,
+            players: &simulator.players,
+            ships: &simulator.ships,
+            dropoffs: &simulator.dropoffs,
+        }
+    }
+}
+
+// What the writer remembers about the previous turn to work out what that turn changed.
+struct Snapshot {
+    halite: Vec<Vec<usize>>,
+    bank: Vec<usize>,
+    ships: Vec<ReplayShip>,
+    dropoff_ids: HashSet<DropoffId>,
+    commands: Vec<Vec<Command>>,
+}
+
+// Writes replays the official visualizer can play. The state at the start of every turn is
+// recorded together with the commands sent that turn; what the turn did (spawns, constructions,
+// collisions, changed cells and deposits) is worked out from the state at the start of the next
+// turn, so any source of game states can be recorded.
+pub struct ReplayWriter {
+    constants: Constants,
+    map_seed: u64,
+    player_names: Vec<String>,
+    width: usize,
+    height: usize,
+    initial_halite: Vec<Vec<usize>>,
+    shipyards: Vec<Position>,
+    initial_bank: Vec<usize>,
+    deposited: Vec<usize>,
+    frames: Vec<Value>,
+    statistics: Value,
+    previous: Option<Snapshot>,
+}
+
+impl ReplayWriter {
+    pub fn new(constants: &Constants, map_seed: u64, player_names: &[String], initial: ReplayState) -> ReplayWriter {
+        let num_players = initial.players.len();
+        let initial_bank: Vec<usize> = initial.players.iter().map(|player| player.halite).collect();
+
+        let initial_frame = json!({
+            "cells": [],
+            "deposited": by_player(&vec![0; num_players]),
+            "energy": by_player(&initial_bank),
+            "entities": {},
+            "events": [],
+            "moves": {},
+        });
+
+        ReplayWriter {
+            constants: constants.clone(),
+            map_seed,
+            player_names: player_names.to_vec(),
+            width: initial.game_map.width,
+            height: initial.game_map.height,
+            initial_halite: halite_grid(initial.game_map),
+            shipyards: initial.players.iter().map(|player| player.shipyard.position).collect(),
+            initial_bank,
+            deposited: vec![0; num_players],
+            frames: vec![initial_frame],
+            statistics: Value::Null,
+            previous: None,
+        }
+    }
+
+    // Records the state at the start of a turn and the commands every player sent for it, indexed
+    // by player id.
+    pub fn record_turn(&mut self, state: ReplayState, commands: &[Vec<Command>]) {
+        self.close_previous_turn(&state);
+
+        let mut moves = serde_json::Map::new();
+        for player in state.players {
+            let player_moves = match commands.get(player.id.0) {
+                Some(player_commands) => player_commands.iter().map(command_json).collect(),
+                None => Vec::new(),
+            };
+            moves.insert(player.id.0.to_string(), Value::Array(player_moves));
+        }
+
+        self.frames.push(json!({
+            "cells": [],
+            "deposited": {},
+            "energy": {},
+            "entities": self.entities_json(&state),
+            "events": [],
+            "moves": moves,
+        }));
+
+        self.previous = Some(Snapshot {
+            halite: halite_grid(state.game_map),
+            bank: state.players.iter().map(|player| player.halite).collect(),
+            ships: replay_ships(&state),
+            dropoff_ids: state.dropoffs.keys().cloned().collect(),
+            commands: commands.to_vec(),
+        });
+    }
+
+    // Records the state after the last turn together with the final standings, best player first.
+    pub fn finish(&mut self, state: ReplayState, rankings: &[PlayerId]) {
+        self.close_previous_turn(&state);
+
+        let bank: Vec<usize> = state.players.iter().map(|player| player.halite).collect();
+        let final_frame = json!({
+            "cells": [],
+            "deposited": by_player(&self.deposited),
+            "energy": by_player(&bank),
+            "entities": self.entities_json(&state),
+            "events": [],
+            "moves": {},
+        });
+        self.frames.push(final_frame);
+
+        let number_turns = self.frames.len() - 2;
+        let player_statistics: Vec<Value> = rankings.iter().enumerate().map(|(rank, player_id)| json!({
+            "player_id": player_id.0,
+            "rank": rank + 1,
+            "final_production": bank[player_id.0],
+            "total_production": self.deposited[player_id.0],
+            "last_turn_alive": number_turns,
+        })).collect();
+
+        self.statistics = json!({
+            "number_turns": number_turns,
+            "player_statistics": player_statistics,
+        });
+    }
+
+    pub fn to_json(&self) -> Value {
+        let constants: Value = serde_json::from_str(&self.constants.to_engine_string(self.map_seed))
+            .expect("Error: replay: constants should serialize to valid JSON.");
+
+        let grid: Vec<Value> = self.initial_halite.iter()
+            .map(|row| Value::Array(row.iter().map(|halite| json!({ "energy": halite })).collect()))
+            .collect();
+
+        let players: Vec<Value> = self.shipyards.iter().enumerate().map(|(player_id, shipyard)| json!({
+            "energy": self.initial_bank[player_id],
+            "entities": [],
+            "factory_location": { "x": shipyard.x, "y": shipyard.y },
+            "name": self.player_names.get(player_id).cloned().unwrap_or_default(),
+            "player_id": player_id,
+        })).collect();
+
+        json!({
+            "ENGINE_VERSION": "1.2",
+            "GAME_CONSTANTS": constants,
+            "REPLAY_FILE_VERSION": 3,
+            "game_statistics": self.statistics,
+            "map_generator_seed": self.map_seed,
+            "number_of_players": self.shipyards.len(),
+            "players": players,
+            "production_map": {
+                "grid": grid,
+                "height": self.height,
+                "map_generator": "basic",
+                "width": self.width,
+            },
+            "full_frames": self.frames,
+        })
+    }
+
+    pub fn write<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let json = self.to_json().to_string();
+        let mut encoder = zstd::Encoder::new(File::create(path)?, 0)?;
+        encoder.write_all(json.as_bytes())?;
+        encoder.finish()?;
+        Ok(())
+    }
+
+    fn close_previous_turn(&mut self, state: &ReplayState) {
+        let previous = match self.previous.take() {
+            Some(previous) => previous,
+            None => return,
+        };
+
+        let mut cells = Vec::new();
+        for (y, row) in previous.halite.iter().enumerate() {
+            for (x, old_halite) in row.iter().enumerate() {
+                let halite = state.game_map.at_position(&Position { x: x as i32, y: y as i32 }).halite;
+                if halite != *old_halite {
+                    cells.push(json!({ "x": x, "y": y, "production": halite }));
+                }
+            }
+        }
+
+        let mut spent = vec![0; previous.bank.len()];
+        let mut events = Vec::new();
+
+        let mut spawned: Vec<&Ship> = state.ships.values()
+            .filter(|ship| !previous.ships.iter().any(|old| old.id == ship.id))
+            .collect();
+        spawned.sort_by_key(|ship| ship.id.0);
+        for ship in spawned {
+            spent[ship.owner.0] += self.constants.ship_cost;
+            events.push(json!({
+                "type": "spawn",
+                "id": ship.id.0,
+                "location": { "x": ship.position.x, "y": ship.position.y },
+                "owner_id": ship.owner.0,
+                "energy": ship.halite,
+            }));
+        }
+
+        let mut constructed: Vec<&Dropoff> = state.dropoffs.values()
+            .filter(|dropoff| !previous.dropoff_ids.contains(&dropoff.id))
+            .collect();
+        constructed.sort_by_key(|dropoff| dropoff.id.0);
+        let mut converted: HashSet<ShipId> = HashSet::new();
+        for dropoff in constructed {
+            let builder = previous.ships.iter().find(|ship| ship.owner == dropoff.owner && ship.position == dropoff.position);
+            if let Some(builder) = builder {
+                let cell_halite = previous.halite[dropoff.position.y as usize][dropoff.position.x as usize];
+                spent[dropoff.owner.0] += self.constants.construction_cost(builder.halite, cell_halite);
+                converted.insert(builder.id);
+            }
+            events.push(json!({
+                "type": "construct",
+                "id": dropoff.id.0,
+                "location": { "x": dropoff.position.x, "y": dropoff.position.y },
+                "owner_id": dropoff.owner.0,
+            }));
+        }
+
+        // Ships that vanished without building anything collided where their last move took them.
+        let mut wrecks: Vec<(Position, Vec<usize>)> = Vec::new();
+        for ship in &previous.ships {
+            if state.ships.contains_key(&ship.id) || converted.contains(&ship.id) {
+                continue;
+            }
+
+            let direction = previous.commands.get(ship.owner.0)
+                .and_then(|commands| commands.iter().filter_map(|command| match *command {
+                    Command::Move(ship_id, direction) if ship_id == ship.id => Some(direction),
+                    _ => None,
+                }).next())
+                .unwrap_or(Direction::Still);
+            let location = state.game_map.normalize(&ship.position.directional_offset(direction));
+
+            match wrecks.iter_mut().find(|(position, _)| *position == location) {
+                Some((_, ship_ids)) => ship_ids.push(ship.id.0),
+                None => wrecks.push((location, vec![ship.id.0])),
+            }
+        }
+        for (location, ship_ids) in wrecks {
+            events.push(json!({
+                "type": "shipwreck",
+                "location": { "x": location.x, "y": location.y },
+                "ships": ship_ids,
+            }));
+        }
+
+        let bank: Vec<usize> = state.players.iter().map(|player| player.halite).collect();
+        for player_id in 0..bank.len().min(previous.bank.len()) {
+            self.deposited[player_id] += (bank[player_id] + spent[player_id]).saturating_sub(previous.bank[player_id]);
+        }
+
+        let frame = self.frames.last_mut().expect("Error: replay: a turn was recorded without a frame.");
+        frame["cells"] = Value::Array(cells);
+        frame["events"] = Value::Array(events);
+        frame["energy"] = by_player(&bank);
+        frame["deposited"] = by_player(&self.deposited);
+    }
+
+    fn entities_json(&self, state: &ReplayState) -> Value {
+        let mut entities = serde_json::Map::new();
+        for player in state.players {
+            let mut player_ships = serde_json::Map::new();
+            for ship_id in &player.ship_ids {
+                let ship = &state.ships[ship_id];
+                player_ships.insert(ship_id.0.to_string(), json!({
+                    "x": ship.position.x,
+                    "y": ship.position.y,
+                    "energy": ship.halite,
+                    "is_inspired": self.is_inspired(state, ship),
+                }));
+            }
+            entities.insert(player.id.0.to_string(), Value::Object(player_ships));
+        }
+        Value::Object(entities)
+    }
+
+    fn is_inspired(&self, state: &ReplayState, ship: &Ship) -> bool {
+        if !self.constants.inspiration_enabled {
+            return false;
+        }
+
+        let nearby_enemies = state.ships.values()
+            .filter(|other| other.owner != ship.owner)
+            .filter(|other| state.game_map.calculate_distance(&ship.position, &other.position) <= self.constants.inspiration_radius)
+            .count();
+        nearby_enemies >= self.constants.inspiration_ship_count
+    }
+}
+
+fn replay_ships(state: &ReplayState) -> Vec<ReplayShip> {
+    let mut ships: Vec<ReplayShip> = state.ships.values()
+        .map(|ship| ReplayShip { owner: ship.owner, id: ship.id, position: ship.position, halite: ship.halite })
+        .collect();
+    ships.sort_by_key(|ship| ship.id.0);
+    ships
+}
+
+fn halite_grid(game_map: &GameMap) -> Vec<Vec<usize>> {
+    (0..game_map.height)
+        .map(|y| (0..game_map.width).map(|x| game_map.at_position(&Position { x: x as i32, y: y as i32 }).halite).collect())
+        .collect()
+}
+
+fn by_player(values: &[usize]) -> Value {
+    Value::Object(values.iter().enumerate().map(|(player_id, value)| (player_id.to_string(), json!(value))).collect())
+}
+
+fn command_json(command: &Command) -> Value {
+    match *command {
+        Command::Spawn => json!({ "type": "g" }),
+        Command::Construct(ship_id) => json!({ "type": "c", "id": ship_id.0 }),
+        Command::Move(ship_id, direction) => json!({
+            "type": "m",
+            "id": ship_id.0,
+            "direction": direction.get_char_encoding().to_string(),
+        }),
+    }
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("Error: replay: {}.", message))
+}
+
+fn field<'a>(value: &'a Value, key: &str) -> io::Result<&'a Value> {
+    value.get(key).ok_or_else(|| invalid(&format!("missing field {}", key)))
+}
+
+fn array(value: &Value) -> io::Result<&Vec<Value>> {
+    value.as_array().ok_or_else(|| invalid("expected an array"))
+}
+
+fn number(value: &Value) -> io::Result<usize> {
+    value.as_u64().map(|n| n as usize).ok_or_else(|| invalid(&format!("expected a number, got {}", value)))
+}
+
+fn parse_key(key: &str) -> io::Result<usize> {
+    key.parse().map_err(|_| invalid(&format!("expected a numeric key, got {}", key)))
+}
+
+fn position(value: &Value) -> io::Result<Position> {
+    let x = number(field(value, "x")?)? as i32;
+    let y = number(field(value, "y")?)? as i32;
+    Ok(Position { x, y })
+}
+
+fn on_map(position: Position, width: usize, height: usize) -> io::Result<Position> {
+    if position.x as usize >= width || position.y as usize >= height {
+        return Err(invalid(&format!("position {} {} is outside the map", position.x, position.y)));
+    }
+    Ok(position)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // What a bot saw at the start of a turn, as (id, owner, position, halite) ships, the halite grid
+    // and the banks, together with the commands sent that turn.
+    struct Seen {
+        ships: Vec<(usize, usize, Position, usize)>,
+        halite: Vec<Vec<usize>>,
+        bank: Vec<usize>,
+        commands: Vec<Vec<Command>>,
+    }
+
+    fn seen(game_map: &GameMap, players: &[Player], ships: &HashMap<ShipId, Ship>, commands: &[Vec<Command>]) -> Seen {
+        let mut ships: Vec<(usize, usize, Position, usize)> = ships.values()
+            .map(|ship| (ship.id.0, ship.owner.0, ship.position, ship.halite))
+            .collect();
+        ships.sort_by_key(|ship| ship.0);
+        Seen {
+            ships,
+            halite: halite_grid(game_map),
+            bank: players.iter().map(|player| player.halite).collect(),
+            commands: commands.to_vec(),
+        }
+    }
+
+    // A short two player game played in the simulator, returned as replay JSON together with what
+    // the bots saw each turn.
+    fn recorded_game() -> (Value, Vec<Seen>) {
+        let halite: Vec<Vec<usize>> = (0..16).map(|y| (0..16).map(|x| (x * 7 + y * 13) % 300).collect()).collect();
+        let players = vec![
+            Player::new(PlayerId(0), Position { x: 4, y: 8 }),
+            Player::new(PlayerId(1), Position { x: 12, y: 8 }),
+        ];
+        let mut simulator = Simulator::new(Constants::default_for_map(16), GameMap::new(16, 16, &halite), players);
+        let names = vec!["a".to_string(), "b".to_string()];
+        let mut writer = ReplayWriter::new(&simulator.constants, 7, &names, ReplayState::from(&simulator));
+
+        let turns = vec![
+            vec![vec![Command::spawn_ship()], vec![Command::spawn_ship()]],
+            vec![vec![Command::move_ship(ShipId(0), Direction::East)], vec![Command::move_ship(ShipId(1), Direction::West)]],
+            vec![vec![Command::move_ship(ShipId(0), Direction::North)], vec![]],
+        ];
+        let mut seen_turns = Vec::new();
+        for commands in &turns {
+            seen_turns.push(seen(&simulator.game_map, &simulator.players, &simulator.ships, commands));
+            writer.record_turn(ReplayState::from(&simulator), commands);
+            simulator.process_turn(commands);
+        }
+        writer.finish(ReplayState::from(&simulator), &simulator.rankings());
+        (writer.to_json(), seen_turns)
+    }
+
+    #[test]
+    fn written_replay_parses_back_to_the_same_turns() {
+        let (json, seen_turns) = recorded_game();
+        let replay = Replay::parse(&json.to_string()).expect("the replay should parse");
+
+        assert_eq!(replay.map_seed, 7);
+        assert_eq!(replay.player_names, vec!["a".to_string(), "b".to_string()]);
+        assert_eq!((replay.width, replay.height, replay.num_turns), (16, 16, seen_turns.len()));
+
+        let turns: Vec<ReplayTurn> = replay.turns().collect();
+        assert_eq!(turns.len(), seen_turns.len());
+        for (turn, expected) in turns.iter().zip(&seen_turns) {
+            let actual = seen(&turn.game_map, &turn.players, &turn.ships, &turn.commands);
+            assert_eq!(actual.ships, expected.ships, "ships on turn {}", turn.turn_number);
+            assert_eq!(actual.halite, expected.halite, "halite on turn {}", turn.turn_number);
+            assert_eq!(actual.bank, expected.bank, "banks on turn {}", turn.turn_number);
+            assert_eq!(actual.commands, expected.commands, "commands on turn {}", turn.turn_number);
+        }
+    }
+
+    #[test]
+    fn single_turn_matches_walking_the_turns() {
+        let (json, seen_turns) = recorded_game();
+        let replay = Replay::parse(&json.to_string()).expect("the replay should parse");
+
+        let turn = replay.turn(3).expect("the replay has a third turn");
+        assert_eq!(turn.turn_number, 3);
+        assert_eq!(seen(&turn.game_map, &turn.players, &turn.ships, &turn.commands).ships, seen_turns[2].ships);
+        assert!(replay.turn(0).is_none());
+        assert!(replay.turn(4).is_none());
+    }
+
+    #[test]
+    fn cell_outside_the_map_is_rejected() {
+        let mut json = recorded_game().0;
+        json["full_frames"][1]["cells"] = json!([{ "x": 16, "y": 0, "production": 5 }]);
+        assert_eq!(Replay::parse(&json.to_string()).err().map(|error| error.kind()), Some(io::ErrorKind::InvalidData));
+    }
+
+    #[test]
+    fn ship_outside_the_map_is_rejected() {
+        let mut json = recorded_game().0;
+        json["full_frames"][2]["entities"]["0"]["0"]["y"] = json!(99);
+        assert_eq!(Replay::parse(&json.to_string()).err().map(|error| error.kind()), Some(io::ErrorKind::InvalidData));
+    }
+
+    #[test]
+    fn owners_that_are_not_players_are_rejected() {
+        let mut json = recorded_game().0;
+        json["full_frames"][2]["entities"]["5"] = json!({});
+        assert_eq!(Replay::parse(&json.to_string()).err().map(|error| error.kind()), Some(io::ErrorKind::InvalidData));
+
+        let mut json = recorded_game().0;
+        json["full_frames"][1]["events"] = json!([{ "type": "construct", "id": 0, "owner_id": 2, "location": { "x": 1, "y": 1 } }]);
+        assert_eq!(Replay::parse(&json.to_string()).err().map(|error| error.kind()), Some(io::ErrorKind::InvalidData));
+    }
+}
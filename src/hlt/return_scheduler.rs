@@ -0,0 +1,94 @@
+use hlt::config::StrategyConfig;
+use hlt::game::Game;
+use hlt::position::Position;
+use hlt::ShipId;
+use std::collections::HashMap;
+
+// Decides when each ship stops mining and heads home for the last time. A ship has to leave early
+// enough to cover the distance to its nearest dropoff, plus the time spent waiting behind the
+// ships that get there before it: a structure can only be entered from its four neighbours, so at
+// most four ships arrive on it per turn, even with our ships allowed to crash into each other on
+// it. Ships closer to a dropoff get the later arrival slots so the rest can leave as late as
+// possible.
+pub struct ReturnScheduler {
+    // Ships arriving on a structure per turn.
+    pub arrivals_per_turn: usize,
+    // Extra turns for ships held up by the cost of moving off a cell or by traffic on the way.
+    pub safety_margin: usize,
+}
+
+impl ReturnScheduler {
+    pub fn new(config: &StrategyConfig) -> ReturnScheduler {
+        ReturnScheduler {
+            arrivals_per_turn: config.return_arrivals_per_turn.max(1),
+            safety_margin: config.return_safety_margin,
+        }
+    }
+
+    // For each of our ships, the number of turns left at which it has to start heading home.
+    pub fn schedule(&self, game: &Game) -> HashMap<ShipId, usize> {
+        let mut queues: HashMap<Position, Vec<(usize, ShipId)>> = HashMap::new();
+        for ship_id in &game.players[game.my_id.0].ship_ids {
+            let ship = &game.ships[ship_id];
+            if let Some(nearest) = game.game_map.nearest_dropoff(&ship.position, game.my_id) {
+                queues.entry(nearest.position).or_default().push((nearest.distance, *ship_id));
+            }
+        }
+
+        let mut schedule = HashMap::new();
+        for queue in queues.values_mut() {
+            queue.sort_by_key(|&(distance, ship_id)| (distance, ship_id.0));
+            for (place, &(distance, ship_id)) in queue.iter().enumerate() {
+                let waiting = place / self.arrivals_per_turn;
+                schedule.insert(ship_id, distance + waiting + self.safety_margin);
+            }
+        }
+        schedule
+    }
+
+    pub fn must_return(&self, game: &Game, schedule: &HashMap<ShipId, usize>, ship_id: ShipId) -> bool {
+        schedule.get(&ship_id).is_some_and(|&turns_left| game.turns_left() <= turns_left)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hlt::constants::Constants;
+    use hlt::game_map::GameMap;
+    use hlt::player::Player;
+    use hlt::PlayerId;
+    use hlt::simulator::Simulator;
+
+    #[test]
+    fn later_arrivals_leave_earlier_at_each_structure() {
+        let players = vec![
+            Player::new(PlayerId(0), Position { x: 8, y: 16 }),
+            Player::new(PlayerId(1), Position { x: 24, y: 16 }),
+        ];
+        let mut simulator = Simulator::new(Constants::default_for_map(32), GameMap::new(32, 32, &vec![vec![0; 32]; 32]), players);
+        simulator.add_dropoff(PlayerId(0), Position { x: 20, y: 28 });
+        let next_to_shipyard = simulator.add_ship(PlayerId(0), Position { x: 8, y: 17 }, 0);
+        let three_away: Vec<ShipId> = [(11, 16), (5, 16), (8, 13), (8, 19), (9, 18)].iter()
+            .map(|&(x, y)| simulator.add_ship(PlayerId(0), Position { x, y }, 0))
+            .collect();
+        let near_dropoff = simulator.add_ship(PlayerId(0), Position { x: 20, y: 30 }, 0);
+        let mut game = Game::from_simulator(&simulator, PlayerId(0));
+
+        let scheduler = ReturnScheduler { arrivals_per_turn: 4, safety_margin: 2 };
+        let schedule = scheduler.schedule(&game);
+
+        // The shipyard takes the closest ship and the first three of the others on the first turn
+        // of arrivals, and the last two a turn later.
+        assert_eq!(schedule[&next_to_shipyard], 1 + 2);
+        let three_away_turns: Vec<usize> = three_away.iter().map(|ship_id| schedule[ship_id]).collect();
+        assert_eq!(three_away_turns, vec![5, 5, 5, 6, 6]);
+        assert_eq!(schedule[&near_dropoff], 2 + 2);
+
+        game.turn_number = game.constants.max_turns - 5;
+        assert!(!scheduler.must_return(&game, &schedule, near_dropoff));
+        assert!(scheduler.must_return(&game, &schedule, three_away[0]));
+        game.turn_number += 1;
+        assert!(scheduler.must_return(&game, &schedule, near_dropoff));
+    }
+}
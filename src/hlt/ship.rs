@@ -31,6 +31,10 @@ impl Ship {
         Command::move_ship(self.id, Direction::Still)
     }
 
+    pub fn new(owner: PlayerId, id: ShipId, position: Position, halite: usize, max_halite: usize) -> Ship {
+        Ship { owner, id, position, halite, max_halite }
+    }
+
     pub fn generate(input: &mut Input, player_id: PlayerId, max_halite: usize) -> Ship {
         input.read_and_parse_line();
         let id = ShipId(input.next_usize());
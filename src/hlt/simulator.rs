@@ -0,0 +1,628 @@
+use hlt::command::Command;
+use hlt::constants::Constants;
+use hlt::direction::Direction;
+use hlt::dropoff::Dropoff;
+use hlt::DropoffId;
+use hlt::game_map::GameMap;
+use hlt::map_cell::Structure;
+use hlt::player::Player;
+use hlt::PlayerId;
+use hlt::position::Position;
+use hlt::ship::Ship;
+use hlt::ShipId;
+use std::collections::HashMap;
+use std::collections::HashSet;
+
+pub enum Event {
+    Spawn { owner: PlayerId, ship_id: ShipId, position: Position },
+    Shipwreck { position: Position, ship_ids: Vec<ShipId> },
+    Construct { owner: PlayerId, dropoff_id: DropoffId, position: Position },
+}
+
+struct Orders {
+    spawn: bool,
+    moves: Vec<(ShipId, Direction)>,
+    constructs: Vec<ShipId>,
+}
+
+// Plays a game of Halite III without the engine. Commands are applied in the same order as the
+// official engine: dropoff construction, movement, spawning, collisions, mining and finally deposits.
+// Inspiration is judged on where the ships are once collisions are resolved, like the engine does,
+// so it applies to mining that same turn and to the cost of moving on the next one.
+pub struct Simulator {
+    pub constants: Constants,
+    pub turn_number: usize,
+    pub players: Vec<Player>,
+    pub ships: HashMap<ShipId, Ship>,
+    pub dropoffs: HashMap<DropoffId, Dropoff>,
+    pub game_map: GameMap,
+    pub inspired: HashSet<ShipId>,
+    pub deposited: Vec<usize>,
+    pub kicked: Vec<bool>,
+    pub last_turn_alive: Vec<usize>,
+    pub events: Vec<Event>,
+    pub changed_cells: Vec<Position>,
+    next_ship_id: usize,
+    next_dropoff_id: usize,
+}
+
+impl Simulator {
+    pub fn new(constants: Constants, game_map: GameMap, players: Vec<Player>) -> Simulator {
+        let mut game_map = game_map;
+        let mut players = players;
+
+        for player in &mut players {
+            player.halite = constants.initial_halite;
+            player.ship_ids.clear();
+            player.dropoff_ids.clear();
+            game_map.at_position_mut(&player.shipyard.position).structure = Structure::Shipyard(player.id);
+        }
+
+        let num_players = players.len();
+
+        Simulator {
+            constants,
+            turn_number: 0,
+            players,
+            ships: HashMap::new(),
+            dropoffs: HashMap::new(),
+            game_map,
+            inspired: HashSet::new(),
+            deposited: vec![0; num_players],
+            kicked: vec![false; num_players],
+            last_turn_alive: vec![0; num_players],
+            events: Vec::new(),
+            changed_cells: Vec::new(),
+            next_ship_id: 0,
+            next_dropoff_id: 0,
+        }
+    }
+
+    // Plays one turn. `commands` is indexed by player id; a player that sends a malformed command,
+    // commands a ship it doesn't own, commands a ship twice or spawns twice is kicked from the game
+    // and loses its ships. Commands the player can't afford are ignored, as the engine does when
+    // strict errors are disabled.
+    pub fn process_turn(&mut self, commands: &[Vec<Command>]) {
+        self.turn_number += 1;
+        self.events.clear();
+        self.changed_cells.clear();
+
+        let mut orders: Vec<Option<Orders>> = Vec::with_capacity(self.players.len());
+        for player_index in 0..self.players.len() {
+            if self.kicked[player_index] {
+                orders.push(None);
+                continue;
+            }
+
+            let player_commands = match commands.get(player_index) {
+                Some(player_commands) => &player_commands[..],
+                None => &[],
+            };
+
+            match self.validate(PlayerId(player_index), player_commands) {
+                Some(player_orders) => orders.push(Some(player_orders)),
+                None => {
+                    self.kick(PlayerId(player_index));
+                    orders.push(None);
+                }
+            }
+        }
+
+        for (player_index, player_orders) in orders.iter().enumerate() {
+            if let Some(player_orders) = player_orders {
+                for ship_id in &player_orders.constructs {
+                    self.construct(PlayerId(player_index), *ship_id);
+                }
+            }
+        }
+
+        let mut moved: HashSet<ShipId> = HashSet::new();
+        for player_orders in orders.iter().flatten() {
+            for (ship_id, direction) in &player_orders.moves {
+                if self.move_ship(*ship_id, *direction) {
+                    moved.insert(*ship_id);
+                }
+            }
+        }
+
+        for (player_index, player_orders) in orders.iter().enumerate() {
+            if let Some(player_orders) = player_orders {
+                if player_orders.spawn {
+                    if let Some(ship_id) = self.spawn(PlayerId(player_index)) {
+                        moved.insert(ship_id);
+                    }
+                }
+            }
+        }
+
+        self.resolve_collisions();
+        self.update_inspiration();
+        self.mine(&moved);
+        self.deposit();
+        self.refresh();
+    }
+
+    pub fn can_play(&self, player_id: PlayerId) -> bool {
+        let player = &self.players[player_id.0];
+        !self.kicked[player_id.0] && (!player.ship_ids.is_empty() || player.halite >= self.constants.ship_cost)
+    }
+
+    pub fn is_finished(&self) -> bool {
+        if self.turn_number >= self.constants.max_turns {
+            return true;
+        }
+
+        let playing = self.players.iter().filter(|player| self.can_play(player.id)).count();
+        if self.players.len() > 1 { playing <= 1 } else { playing == 0 }
+    }
+
+    // Players from first to last place: players that stayed in the game longer rank higher, then
+    // the one with the most halite wins.
+    pub fn rankings(&self) -> Vec<PlayerId> {
+        let mut ranked: Vec<PlayerId> = self.players.iter().map(|player| player.id).collect();
+        ranked.sort_by(|a, b| {
+            self.last_turn_alive[b.0].cmp(&self.last_turn_alive[a.0])
+                .then_with(|| self.players[b.0].halite.cmp(&self.players[a.0].halite))
+                .then_with(|| a.0.cmp(&b.0))
+        });
+        ranked
+    }
+
+    // Removes a player and its ships from the game, as the engine does when a bot errors or times out.
+    pub fn kick(&mut self, player_id: PlayerId) {
+        self.kicked[player_id.0] = true;
+        self.ships.retain(|_, ship| ship.owner != player_id);
+        self.players[player_id.0].ship_ids.clear();
+        for y in 0..self.game_map.height {
+            for x in 0..self.game_map.width {
+                let cell = self.game_map.at_position_mut(&Position { x: x as i32, y: y as i32 });
+                if let Some(ship_id) = cell.ship {
+                    if !self.ships.contains_key(&ship_id) {
+                        cell.ship = None;
+                    }
+                }
+            }
+        }
+    }
+
+    // The lines the engine sends a bot before the first turn, in the format `Game::new` reads.
+    pub fn initial_message(&self, player_id: PlayerId, game_seed: u64) -> String {
+        let mut message = String::new();
+        message.push_str(&self.constants.to_engine_string(game_seed));
+        message.push('\n');
+        message.push_str(&format!("{} {}\n", self.players.len(), player_id.0));
+
+        for player in &self.players {
+            let shipyard = &player.shipyard.position;
+            message.push_str(&format!("{} {} {}\n", player.id.0, shipyard.x, shipyard.y));
+        }
+
+        message.push_str(&format!("{} {}\n", self.game_map.width, self.game_map.height));
+        for y in 0..self.game_map.height {
+            let row: Vec<String> = (0..self.game_map.width)
+                .map(|x| self.game_map.at_position(&Position { x: x as i32, y: y as i32 }).halite.to_string())
+                .collect();
+            message.push_str(&row.join(" "));
+            message.push('\n');
+        }
+
+        message
+    }
+
+    // The lines the engine sends every bot at the start of the next turn, in the format
+    // `Game::update_frame` reads. Only the cells the previous turn changed are sent.
+    pub fn frame_message(&self) -> String {
+        let mut message = format!("{}\n", self.turn_number + 1);
+
+        for player in &self.players {
+            message.push_str(&format!("{} {} {} {}\n", player.id.0, player.ship_ids.len(), player.dropoff_ids.len(), player.halite));
+            for ship_id in &player.ship_ids {
+                let ship = &self.ships[ship_id];
+                message.push_str(&format!("{} {} {} {}\n", ship_id.0, ship.position.x, ship.position.y, ship.halite));
+            }
+            for dropoff_id in &player.dropoff_ids {
+                let dropoff = &self.dropoffs[dropoff_id];
+                message.push_str(&format!("{} {} {}\n", dropoff_id.0, dropoff.position.x, dropoff.position.y));
+            }
+        }
+
+        let mut changed_cells: Vec<Position> = Vec::new();
+        for position in &self.changed_cells {
+            if !changed_cells.contains(position) {
+                changed_cells.push(*position);
+            }
+        }
+
+        message.push_str(&format!("{}\n", changed_cells.len()));
+        for position in changed_cells {
+            let halite = self.game_map.at_position(&position).halite;
+            message.push_str(&format!("{} {} {}\n", position.x, position.y, halite));
+        }
+
+        message
+    }
+
+    fn validate(&self, player_id: PlayerId, commands: &[Command]) -> Option<Orders> {
+        let mut orders = Orders { spawn: false, moves: Vec::new(), constructs: Vec::new() };
+        let mut commanded: HashSet<ShipId> = HashSet::new();
+
+        for command in commands {
+            if let Some(ship_id) = command.ship_id() {
+                match self.ships.get(&ship_id) {
+                    Some(ship) if ship.owner == player_id => (),
+                    _ => return None,
+                }
+                if !commanded.insert(ship_id) {
+                    return None;
+                }
+            }
+
+            match *command {
+                Command::Spawn => {
+                    if orders.spawn {
+                        return None;
+                    }
+                    orders.spawn = true;
+                },
+                Command::Move(ship_id, direction) => orders.moves.push((ship_id, direction)),
+                Command::Construct(ship_id) => orders.constructs.push(ship_id),
+            }
+        }
+
+        Some(orders)
+    }
+
+    fn construct(&mut self, player_id: PlayerId, ship_id: ShipId) {
+        let (position, ship_halite) = {
+            let ship = &self.ships[&ship_id];
+            (ship.position, ship.halite)
+        };
+
+        let cell_halite = {
+            let cell = self.game_map.at_position(&position);
+            if cell.has_structure() {
+                return;
+            }
+            cell.halite
+        };
+
+        let cost = self.constants.construction_cost(ship_halite, cell_halite);
+        if self.players[player_id.0].halite < cost {
+            return;
+        }
+
+        self.players[player_id.0].halite -= cost;
+        self.ships.remove(&ship_id);
+
+        let dropoff_id = DropoffId(self.next_dropoff_id);
+        self.next_dropoff_id += 1;
+        self.dropoffs.insert(dropoff_id, Dropoff { owner: player_id, id: dropoff_id, position });
+        self.players[player_id.0].dropoff_ids.push(dropoff_id);
+
+        let cell = self.game_map.at_position_mut(&position);
+        cell.structure = Structure::Dropoff(dropoff_id);
+        if cell.halite > 0 {
+            cell.halite = 0;
+            self.changed_cells.push(position);
+        }
+
+        self.events.push(Event::Construct { owner: player_id, dropoff_id, position });
+    }
+
+    fn move_ship(&mut self, ship_id: ShipId, direction: Direction) -> bool {
+        if direction == Direction::Still || !self.ships.contains_key(&ship_id) {
+            return false;
+        }
+
+        let inspired = self.inspired.contains(&ship_id);
+        let (position, cost) = {
+            let ship = &self.ships[&ship_id];
+            let cell_halite = self.game_map.at_position(&ship.position).halite;
+            (ship.position, self.constants.move_cost(cell_halite, inspired))
+        };
+
+        let target = self.game_map.normalize(&position.directional_offset(direction));
+        let ship = self.ships.get_mut(&ship_id).unwrap();
+        if ship.halite < cost {
+            return false;
+        }
+
+        ship.halite -= cost;
+        ship.position = target;
+        true
+    }
+
+    fn spawn(&mut self, player_id: PlayerId) -> Option<ShipId> {
+        if self.players[player_id.0].halite < self.constants.ship_cost {
+            return None;
+        }
+
+        self.players[player_id.0].halite -= self.constants.ship_cost;
+
+        let ship_id = ShipId(self.next_ship_id);
+        self.next_ship_id += 1;
+        let position = self.players[player_id.0].shipyard.position;
+        self.ships.insert(ship_id, Ship::new(player_id, ship_id, position, 0, self.constants.max_halite));
+
+        self.events.push(Event::Spawn { owner: player_id, ship_id, position });
+        Some(ship_id)
+    }
+
+    fn resolve_collisions(&mut self) {
+        let mut by_position: HashMap<Position, Vec<ShipId>> = HashMap::new();
+        for ship in self.ships.values() {
+            by_position.entry(ship.position).or_default().push(ship.id);
+        }
+
+        let mut wrecks: Vec<(Position, Vec<ShipId>)> = by_position.into_iter()
+            .filter(|(_, ship_ids)| ship_ids.len() > 1)
+            .collect();
+        wrecks.sort_by_key(|(position, _)| (position.y, position.x));
+
+        for (position, mut ship_ids) in wrecks {
+            ship_ids.sort_by_key(|ship_id| ship_id.0);
+
+            let mut cargo = 0;
+            for ship_id in &ship_ids {
+                cargo += self.ships.remove(ship_id).unwrap().halite;
+            }
+
+            match self.structure_owner(&position) {
+                Some(owner) => {
+                    self.players[owner.0].halite += cargo;
+                    self.deposited[owner.0] += cargo;
+                },
+                None => {
+                    if cargo > 0 {
+                        self.game_map.at_position_mut(&position).halite += cargo;
+                        self.changed_cells.push(position);
+                    }
+                },
+            }
+
+            self.events.push(Event::Shipwreck { position, ship_ids });
+        }
+    }
+
+    fn mine(&mut self, moved: &HashSet<ShipId>) {
+        for ship in self.ships.values_mut() {
+            if moved.contains(&ship.id) {
+                continue;
+            }
+
+            let inspired = self.inspired.contains(&ship.id);
+            let cell = self.game_map.at_position_mut(&ship.position);
+            let (extracted, gained) = self.constants.extraction(cell.halite, ship.halite, inspired);
+            if extracted == 0 && gained == 0 {
+                continue;
+            }
+
+            cell.halite -= extracted;
+            ship.halite += gained;
+            if extracted > 0 {
+                self.changed_cells.push(ship.position);
+            }
+        }
+    }
+
+    fn deposit(&mut self) {
+        let mut deposits: Vec<(PlayerId, ShipId)> = Vec::new();
+        for ship in self.ships.values() {
+            if self.structure_owner(&ship.position) == Some(ship.owner) && ship.halite > 0 {
+                deposits.push((ship.owner, ship.id));
+            }
+        }
+
+        for (owner, ship_id) in deposits {
+            let ship = self.ships.get_mut(&ship_id).unwrap();
+            self.players[owner.0].halite += ship.halite;
+            self.deposited[owner.0] += ship.halite;
+            ship.halite = 0;
+        }
+    }
+
+    fn update_inspiration(&mut self) {
+        self.inspired.clear();
+        if !self.constants.inspiration_enabled {
+            return;
+        }
+
+        for ship in self.ships.values() {
+            let nearby_enemies = self.ships.values()
+                .filter(|other| other.owner != ship.owner)
+                .filter(|other| self.game_map.calculate_distance(&ship.position, &other.position) <= self.constants.inspiration_radius)
+                .count();
+
+            if nearby_enemies >= self.constants.inspiration_ship_count {
+                self.inspired.insert(ship.id);
+            }
+        }
+    }
+
+    fn structure_owner(&self, position: &Position) -> Option<PlayerId> {
+        match self.game_map.at_position(position).structure {
+            Structure::None => None,
+            Structure::Shipyard(owner) => Some(owner),
+            Structure::Dropoff(dropoff_id) => Some(self.dropoffs[&dropoff_id].owner),
+        }
+    }
+
+    // Puts a ship on the map, for setting up a position by hand in tests.
+    #[cfg(test)]
+    pub fn add_ship(&mut self, owner: PlayerId, position: Position, halite: usize) -> ShipId {
+        let ship_id = ShipId(self.next_ship_id);
+        self.next_ship_id += 1;
+        self.ships.insert(ship_id, Ship::new(owner, ship_id, position, halite, self.constants.max_halite));
+        self.refresh();
+        ship_id
+    }
+
+    // Puts a dropoff on the map, for setting up a position by hand in tests.
+    #[cfg(test)]
+    pub fn add_dropoff(&mut self, owner: PlayerId, position: Position) -> DropoffId {
+        let dropoff_id = DropoffId(self.next_dropoff_id);
+        self.next_dropoff_id += 1;
+        self.dropoffs.insert(dropoff_id, Dropoff { owner, id: dropoff_id, position });
+        self.players[owner.0].dropoff_ids.push(dropoff_id);
+        self.game_map.at_position_mut(&position).structure = Structure::Dropoff(dropoff_id);
+        dropoff_id
+    }
+
+    // Brings the players' ship lists, the map occupancy and the elimination turns up to date with
+    // the ships that survived the turn.
+    fn refresh(&mut self) {
+        for y in 0..self.game_map.height {
+            for x in 0..self.game_map.width {
+                self.game_map.at_position_mut(&Position { x: x as i32, y: y as i32 }).ship = None;
+            }
+        }
+
+        for player in &mut self.players {
+            player.ship_ids.clear();
+        }
+
+        let mut ship_ids: Vec<ShipId> = self.ships.keys().cloned().collect();
+        ship_ids.sort_by_key(|ship_id| ship_id.0);
+        for ship_id in ship_ids {
+            let ship = &self.ships[&ship_id];
+            self.players[ship.owner.0].ship_ids.push(ship_id);
+            self.game_map.at_position_mut(&ship.position).mark_unsafe(ship_id);
+        }
+
+        for player_index in 0..self.players.len() {
+            if self.can_play(PlayerId(player_index)) {
+                self.last_turn_alive[player_index] = self.turn_number;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Two players on a 32x32 map with the same halite on every cell.
+    fn simulator(cell_halite: usize) -> Simulator {
+        let constants = Constants::default_for_map(32);
+        let game_map = GameMap::new(32, 32, &vec![vec![cell_halite; 32]; 32]);
+        let players = vec![
+            Player::new(PlayerId(0), Position { x: 8, y: 16 }),
+            Player::new(PlayerId(1), Position { x: 24, y: 16 }),
+        ];
+        Simulator::new(constants, game_map, players)
+    }
+
+    fn add_ship(simulator: &mut Simulator, owner: usize, x: i32, y: i32, halite: usize) -> ShipId {
+        simulator.add_ship(PlayerId(owner), Position { x, y }, halite)
+    }
+
+    #[test]
+    fn moving_costs_a_tenth_of_the_cell_rounded_down() {
+        let mut simulator = simulator(155);
+        let ship_id = add_ship(&mut simulator, 0, 3, 3, 100);
+
+        simulator.process_turn(&[vec![Command::Move(ship_id, Direction::East)], vec![]]);
+
+        let ship = &simulator.ships[&ship_id];
+        assert_eq!(ship.position, Position { x: 4, y: 3 });
+        assert_eq!(ship.halite, 85);
+    }
+
+    #[test]
+    fn ship_that_cannot_pay_to_move_stays_and_mines() {
+        let mut simulator = simulator(155);
+        let ship_id = add_ship(&mut simulator, 0, 3, 3, 10);
+
+        simulator.process_turn(&[vec![Command::Move(ship_id, Direction::East)], vec![]]);
+
+        let ship = &simulator.ships[&ship_id];
+        assert_eq!(ship.position, Position { x: 3, y: 3 });
+        assert_eq!(ship.halite, 10 + 39);
+        assert_eq!(simulator.game_map.at_position(&Position { x: 3, y: 3 }).halite, 155 - 39);
+    }
+
+    #[test]
+    fn inspired_ship_gets_the_bonus_on_top_of_what_it_extracts() {
+        let mut simulator = simulator(100);
+        let inspired = add_ship(&mut simulator, 0, 5, 5, 0);
+        let enemy = add_ship(&mut simulator, 1, 6, 5, 0);
+        add_ship(&mut simulator, 1, 5, 7, 0);
+
+        simulator.process_turn(&[vec![], vec![]]);
+
+        assert_eq!(simulator.ships[&inspired].halite, 25 + 50);
+        assert_eq!(simulator.game_map.at_position(&Position { x: 5, y: 5 }).halite, 75);
+        // Only one enemy is near the other player's ships, which isn't enough.
+        assert_eq!(simulator.ships[&enemy].halite, 25);
+    }
+
+    #[test]
+    fn enemies_moving_into_range_inspire_a_ship_on_the_same_turn() {
+        let mut simulator = simulator(100);
+        let miner = add_ship(&mut simulator, 0, 5, 5, 0);
+        let east = add_ship(&mut simulator, 1, 10, 5, 50);
+        let south = add_ship(&mut simulator, 1, 5, 10, 50);
+
+        simulator.process_turn(&[vec![], vec![Command::Move(east, Direction::West), Command::Move(south, Direction::North)]]);
+
+        assert_eq!(simulator.ships[&east].position, Position { x: 9, y: 5 });
+        assert_eq!(simulator.ships[&south].position, Position { x: 5, y: 9 });
+        assert!(simulator.inspired.contains(&miner));
+        assert_eq!(simulator.ships[&miner].halite, 25 + 50);
+    }
+
+    #[test]
+    fn cargo_of_ships_colliding_on_a_structure_goes_to_its_owner() {
+        let mut simulator = simulator(0);
+        let ours = add_ship(&mut simulator, 0, 7, 16, 300);
+        let theirs = add_ship(&mut simulator, 1, 9, 16, 200);
+
+        simulator.process_turn(&[vec![Command::Move(ours, Direction::East)], vec![Command::Move(theirs, Direction::West)]]);
+
+        assert!(simulator.ships.is_empty());
+        assert_eq!(simulator.players[0].halite, 5000 + 500);
+        assert_eq!(simulator.players[1].halite, 5000);
+        assert_eq!(simulator.deposited, vec![500, 0]);
+    }
+
+    #[test]
+    fn cargo_of_ships_colliding_elsewhere_drops_on_the_cell() {
+        let mut simulator = simulator(0);
+        let ours = add_ship(&mut simulator, 0, 4, 4, 300);
+        let theirs = add_ship(&mut simulator, 1, 6, 4, 200);
+
+        simulator.process_turn(&[vec![Command::Move(ours, Direction::East)], vec![Command::Move(theirs, Direction::West)]]);
+
+        assert!(simulator.ships.is_empty());
+        assert_eq!(simulator.game_map.at_position(&Position { x: 5, y: 4 }).halite, 500);
+        assert_eq!(simulator.players[0].halite, 5000);
+    }
+
+    #[test]
+    fn building_a_dropoff_credits_the_cargo_and_the_cell() {
+        let mut simulator = simulator(400);
+        let ship_id = add_ship(&mut simulator, 0, 5, 5, 600);
+
+        simulator.process_turn(&[vec![Command::Construct(ship_id)], vec![]]);
+
+        assert!(!simulator.ships.contains_key(&ship_id));
+        assert_eq!(simulator.players[0].halite, 5000 - 3000);
+        assert_eq!(simulator.players[0].dropoff_ids.len(), 1);
+        let cell = simulator.game_map.at_position(&Position { x: 5, y: 5 });
+        assert_eq!(cell.halite, 0);
+        assert!(cell.has_structure());
+    }
+
+    #[test]
+    fn dropoff_the_bank_cannot_cover_is_not_built() {
+        let mut simulator = simulator(400);
+        let ship_id = add_ship(&mut simulator, 0, 5, 5, 600);
+        simulator.players[0].halite = 2999;
+
+        simulator.process_turn(&[vec![Command::Construct(ship_id)], vec![]]);
+
+        assert!(simulator.ships.contains_key(&ship_id));
+        assert_eq!(simulator.players[0].halite, 2999);
+        assert!(simulator.players[0].dropoff_ids.is_empty());
+    }
+}
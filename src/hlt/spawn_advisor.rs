@@ -0,0 +1,135 @@
+use hlt::config::StrategyConfig;
+use hlt::game::Game;
+
+// Decides whether a new ship is worth its cost. The estimate plays the rest of the game forward a
+// turn at a time: every ship on the map, ours and theirs, mines at a rate proportional to how much
+// halite is left per cell, and the map drains by what all of them take. A new ship's worth is how
+// much more our fleet brings home with it than without it, so it shrinks as the map empties, as
+// the other fleets grow and as the game runs out of turns.
+pub struct SpawnAdvisor {
+    // Halite a ship brings home per turn, as a share of what it would extract each turn from a cell
+    // holding the map's average halite. Covers the turns spent travelling and delivering.
+    pub mining_efficiency: f64,
+    // Cells holding less than this aren't worth mining and don't count towards what is left.
+    pub min_cell_halite: usize,
+    // How many times its cost a new ship has to be expected to return.
+    pub required_return: f64,
+    // Turns a new ship spends reaching the halite, as a share of the map's width.
+    pub startup_share: f64,
+}
+
+impl SpawnAdvisor {
+    pub fn new(config: &StrategyConfig) -> SpawnAdvisor {
+        SpawnAdvisor {
+            mining_efficiency: config.spawn_mining_efficiency,
+            min_cell_halite: config.spawn_min_cell_halite,
+            required_return: config.spawn_required_return,
+            startup_share: config.spawn_startup_share,
+        }
+    }
+
+    // Halite a ship spawned this turn is expected to add to our deliveries by the end of the game.
+    pub fn expected_return(&self, game: &Game) -> f64 {
+        let ours = game.players[game.my_id.0].ship_ids.len() as f64;
+        let theirs = (game.ships.len() as f64 - ours).max(0.0);
+        // A new ship first has to reach the halite, which is further away on bigger maps.
+        let startup_turns = (game.game_map.width as f64 * self.startup_share) as usize;
+
+        let with_ship = self.our_deliveries(game, ours, theirs, Some(startup_turns));
+        let without_ship = self.our_deliveries(game, ours, theirs, None);
+        with_ship - without_ship
+    }
+
+    pub fn should_spawn(&self, game: &Game) -> bool {
+        self.expected_return(game) >= game.constants.ship_cost as f64 * self.required_return
+    }
+
+    // What our fleet delivers over the remaining turns, with an extra ship joining after
+    // `new_ship_after` turns if given.
+    fn our_deliveries(&self, game: &Game, ours: f64, theirs: f64, new_ship_after: Option<usize>) -> f64 {
+        let cells = (game.game_map.width * game.game_map.height) as f64;
+        let extract_ratio = game.constants.extract_ratio as f64;
+        let mut halite = game.game_map.total_halite(self.min_cell_halite) as f64;
+        let mut delivered = 0.0;
+
+        for turn in 0..game.turns_left() {
+            let new_ship = match new_ship_after {
+                Some(after) if turn >= after => 1.0,
+                _ => 0.0,
+            };
+            let fleet = ours + theirs + new_ship;
+            if halite <= 0.0 {
+                break;
+            }
+            if fleet == 0.0 {
+                // Nobody is mining yet, e.g. while the first ship makes its way out.
+                continue;
+            }
+
+            let per_ship = halite / cells / extract_ratio * self.mining_efficiency;
+            let taken = (per_ship * fleet).min(halite);
+            delivered += taken * (ours + new_ship) / fleet;
+            halite -= taken;
+        }
+
+        delivered
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hlt::constants::Constants;
+    use hlt::game_map::GameMap;
+    use hlt::player::Player;
+    use hlt::PlayerId;
+    use hlt::position::Position;
+    use hlt::simulator::Simulator;
+
+    // No ships yet on a map with 100 halite on every cell, with `turns_left` turns to go.
+    fn game(turns_left: usize) -> Game {
+        let players = vec![
+            Player::new(PlayerId(0), Position { x: 8, y: 16 }),
+            Player::new(PlayerId(1), Position { x: 24, y: 16 }),
+        ];
+        let simulator = Simulator::new(Constants::default_for_map(32), GameMap::new(32, 32, &vec![vec![100; 32]; 32]), players);
+        let mut game = Game::from_simulator(&simulator, PlayerId(0));
+        game.turn_number = game.constants.max_turns - turns_left;
+        game
+    }
+
+    fn advisor() -> SpawnAdvisor {
+        SpawnAdvisor { mining_efficiency: 1.0, min_cell_halite: 0, required_return: 1.0, startup_share: 0.0 }
+    }
+
+    #[test]
+    fn a_lone_ship_mines_a_quarter_of_the_average_cell_per_turn() {
+        // 100 / 4 from the average cell on the first turn, and a little less once that is gone.
+        assert_eq!(advisor().expected_return(&game(1)), 25.0);
+        let second_turn = (102400.0 - 25.0) / 1024.0 / 4.0;
+        assert!((advisor().expected_return(&game(2)) - (25.0 + second_turn)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn spawns_once_the_return_covers_the_cost() {
+        let mut game = game(1);
+
+        game.constants.ship_cost = 25;
+        assert!(advisor().should_spawn(&game));
+        game.constants.ship_cost = 26;
+        assert!(!advisor().should_spawn(&game));
+
+        let demanding = SpawnAdvisor { required_return: 1.1, ..advisor() };
+        game.constants.ship_cost = 25;
+        assert!(!demanding.should_spawn(&game));
+    }
+
+    #[test]
+    fn no_spawn_when_the_ship_arrives_after_the_game_ends() {
+        // A quarter of the 32 cell width is 8 turns of travel.
+        let late = SpawnAdvisor { startup_share: 0.25, ..advisor() };
+        assert_eq!(late.expected_return(&game(8)), 0.0);
+        assert!(late.expected_return(&game(9)) > 0.0);
+        assert!(!late.should_spawn(&game(8)));
+    }
+}
@@ -0,0 +1,108 @@
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::io::stdin;
+use std::io::stdout;
+use std::io::BufRead;
+use std::io::Write;
+use std::rc::Rc;
+
+// The connection between a bot and whatever runs the game. Lines are exchanged without their
+// trailing newline.
+pub trait Transport {
+    // Returns None once the other side has closed the connection.
+    fn read_line(&mut self) -> Option<String>;
+
+    fn write_line(&mut self, line: &str);
+}
+
+// Talks to the engine over the bot's own stdin and stdout, as on the game servers.
+pub struct StdioTransport;
+
+impl Transport for StdioTransport {
+    fn read_line(&mut self) -> Option<String> {
+        let mut buf = String::new();
+        match stdin().read_line(&mut buf) {
+            Ok(0) | Err(_) => None,
+            Ok(_) => Some(trim_newline(buf)),
+        }
+    }
+
+    fn write_line(&mut self, line: &str) {
+        let stdout = stdout();
+        let mut handle = stdout.lock();
+        writeln!(handle, "{}", line).unwrap();
+        handle.flush().unwrap();
+    }
+}
+
+// Keeps both directions in memory so tests and in-process games can drive a `Game`. Clones share
+// the same buffers: hand one clone to the game and keep another to feed it and read its replies.
+#[derive(Clone)]
+pub struct BufferTransport {
+    input: Rc<RefCell<VecDeque<String>>>,
+    output: Rc<RefCell<Vec<String>>>,
+}
+
+impl BufferTransport {
+    pub fn new() -> BufferTransport {
+        BufferTransport { input: Rc::new(RefCell::new(VecDeque::new())), output: Rc::new(RefCell::new(Vec::new())) }
+    }
+
+    // Queues text for the game to read, one entry per line.
+    pub fn push_input(&self, text: &str) {
+        let mut input = self.input.borrow_mut();
+        for line in text.lines() {
+            input.push_back(line.to_string());
+        }
+    }
+
+    // Takes every line the game has written so far.
+    pub fn take_output(&self) -> Vec<String> {
+        self.output.borrow_mut().drain(..).collect()
+    }
+}
+
+impl Transport for BufferTransport {
+    fn read_line(&mut self) -> Option<String> {
+        self.input.borrow_mut().pop_front()
+    }
+
+    fn write_line(&mut self, line: &str) {
+        self.output.borrow_mut().push(line.to_string());
+    }
+}
+
+// Talks over any reader and writer pair, like the pipes of a child process or a socket to a
+// local engine.
+pub struct PipeTransport<R: BufRead, W: Write> {
+    reader: R,
+    writer: W,
+}
+
+impl<R: BufRead, W: Write> PipeTransport<R, W> {
+    pub fn new(reader: R, writer: W) -> PipeTransport<R, W> {
+        PipeTransport { reader, writer }
+    }
+}
+
+impl<R: BufRead, W: Write> Transport for PipeTransport<R, W> {
+    fn read_line(&mut self) -> Option<String> {
+        let mut buf = String::new();
+        match self.reader.read_line(&mut buf) {
+            Ok(0) | Err(_) => None,
+            Ok(_) => Some(trim_newline(buf)),
+        }
+    }
+
+    fn write_line(&mut self, line: &str) {
+        writeln!(self.writer, "{}", line).unwrap();
+        self.writer.flush().unwrap();
+    }
+}
+
+fn trim_newline(mut line: String) -> String {
+    while line.ends_with('\n') || line.ends_with('\r') {
+        line.pop();
+    }
+    line
+}
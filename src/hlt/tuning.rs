@@ -0,0 +1,223 @@
+use rand::prng::XorShiftRng;
+use rand::Rng;
+use rand::SeedableRng;
+use serde_json;
+use serde_json::json;
+use serde_json::Value;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+// A `StrategyConfig` setting the tuner may change, with the range it is searched in.
+pub struct TunableParameter {
+    pub name: &'static str,
+    pub min: f64,
+    pub max: f64,
+    pub integer: bool,
+}
+
+pub const TUNABLE_PARAMETERS: &[TunableParameter] = &[
+    TunableParameter { name: "return_margin", min: 0.0, max: 600.0, integer: true },
+    TunableParameter { name: "rich_halite", min: 5.0, max: 100.0, integer: true },
+    TunableParameter { name: "max_search_distance", min: 6.0, max: 24.0, integer: true },
+    TunableParameter { name: "max_mining_turns", min: 4.0, max: 24.0, integer: true },
+    TunableParameter { name: "first_dropoff_turn", min: 10.0, max: 150.0, integer: true },
+    TunableParameter { name: "ships_per_dropoff", min: 3.0, max: 15.0, integer: true },
+    TunableParameter { name: "max_dropoffs", min: 0.0, max: 6.0, integer: true },
+    TunableParameter { name: "dropoff_min_turns_left", min: 50.0, max: 200.0, integer: true },
+    TunableParameter { name: "dropoff_zone_radius", min: 2.0, max: 8.0, integer: true },
+    TunableParameter { name: "dropoff_spacing", min: 2.0, max: 5.0, integer: true },
+    TunableParameter { name: "dropoff_crowding", min: 0.0, max: 1.0, integer: false },
+    TunableParameter { name: "dropoff_builder_reach", min: 1.0, max: 3.0, integer: false },
+    TunableParameter { name: "dropoff_max_build_turns", min: 20.0, max: 100.0, integer: true },
+    TunableParameter { name: "spawn_mining_efficiency", min: 0.1, max: 1.0, integer: false },
+    TunableParameter { name: "spawn_min_cell_halite", min: 0.0, max: 100.0, integer: true },
+    TunableParameter { name: "spawn_required_return", min: 0.5, max: 2.0, integer: false },
+    TunableParameter { name: "spawn_startup_share", min: 0.1, max: 0.5, integer: false },
+    TunableParameter { name: "return_safety_margin", min: 0.0, max: 10.0, integer: true },
+];
+
+pub fn tunable_parameter(name: &str) -> Option<&'static TunableParameter> {
+    TUNABLE_PARAMETERS.iter().find(|parameter| parameter.name == name)
+}
+
+impl TunableParameter {
+    // Positions within the range, from 0 to 1, which is the space the optimizer works in so that
+    // one step size suits every parameter.
+    fn normalize(&self, value: f64) -> f64 {
+        ((value - self.min) / (self.max - self.min)).clamp(0.0, 1.0)
+    }
+
+    fn denormalize(&self, position: f64) -> f64 {
+        let value = self.min + position.clamp(0.0, 1.0) * (self.max - self.min);
+        if self.integer { value.round() } else { value }
+    }
+
+    fn format(&self, value: f64) -> String {
+        if self.integer { format!("{}", value as i64) } else { format!("{:.4}", value) }
+    }
+}
+
+// Simultaneous perturbation stochastic approximation. Every iteration nudges all parameters at
+// once by a random sign in each, plays both nudged settings and steps along the difference in
+// score. Two evaluations per iteration estimate the gradient however many parameters there are,
+// and the shrinking gains average out the noise of game results over the iterations.
+//
+// The gains follow Spall's guidelines: `a_k = a / (k + 1 + A)^0.602` and `c_k = c / (k + 1)^0.101`.
+pub struct Spsa {
+    parameters: Vec<&'static TunableParameter>,
+    positions: Vec<f64>,
+    deltas: Vec<f64>,
+    iteration: usize,
+    step_gain: f64,
+    perturbation_gain: f64,
+    stability: f64,
+    rng: XorShiftRng,
+}
+
+impl Spsa {
+    // `start` holds the parameters' starting values. `iterations` is how many iterations are
+    // planned, which sets how soon the step size starts shrinking.
+    pub fn new(parameters: Vec<&'static TunableParameter>, start: &[f64], iterations: usize, seed: u64) -> Spsa {
+        let positions = parameters.iter().zip(start).map(|(parameter, value)| parameter.normalize(*value)).collect();
+        let stability = (iterations as f64 / 10.0).max(1.0);
+        Spsa {
+            deltas: vec![0.0; parameters.len()],
+            parameters,
+            positions,
+            iteration: 0,
+            // Scores are halite shares, so a difference of 0.1 between the two sides moves the
+            // parameters about a twentieth of their range in the first iteration.
+            step_gain: 0.1 * (1.0 + stability).powf(0.602),
+            perturbation_gain: 0.1,
+            stability,
+            rng: XorShiftRng::seed_from_u64(seed),
+        }
+    }
+
+    pub fn iteration(&self) -> usize {
+        self.iteration
+    }
+
+    // Draws the next perturbation and returns the values to play on the plus and minus side.
+    pub fn perturb(&mut self) -> (Vec<f64>, Vec<f64>) {
+        let perturbation = self.perturbation_gain / (self.iteration as f64 + 1.0).powf(0.101);
+        let rng = &mut self.rng;
+        self.deltas = self.parameters.iter().map(|_| if rng.gen::<bool>() { 1.0 } else { -1.0 }).collect();
+
+        let side = |sign: f64| -> Vec<f64> {
+            self.parameters.iter().zip(&self.positions).zip(&self.deltas)
+                .map(|((parameter, position), delta)| parameter.denormalize(position + sign * perturbation * delta))
+                .collect()
+        };
+        (side(1.0), side(-1.0))
+    }
+
+    // Steps towards the better side given the scores of the last perturbation. Higher is better.
+    pub fn update(&mut self, plus_score: f64, minus_score: f64) {
+        let k = self.iteration as f64;
+        let perturbation = self.perturbation_gain / (k + 1.0).powf(0.101);
+        let step = self.step_gain / (k + 1.0 + self.stability).powf(0.602);
+
+        for (position, delta) in self.positions.iter_mut().zip(&self.deltas) {
+            let gradient = (plus_score - minus_score) / (2.0 * perturbation * delta);
+            *position = (*position + step * gradient).clamp(0.0, 1.0);
+        }
+        self.iteration += 1;
+    }
+
+    pub fn values(&self) -> Vec<f64> {
+        self.parameters.iter().zip(&self.positions).map(|(parameter, position)| parameter.denormalize(*position)).collect()
+    }
+
+    // The values as `name=value` settings for the bot's `--set` flag.
+    pub fn settings(&self, values: &[f64]) -> Vec<String> {
+        self.parameters.iter().zip(values)
+            .map(|(parameter, value)| format!("{}={}", parameter.name, parameter.format(*value)))
+            .collect()
+    }
+
+    // Writes the values into the config file at `path` as settings of the profile for the given
+    // map sizes and player count, adding the profile if the file has none for exactly those.
+    // Everything else in the file, including the profile's other settings, is kept.
+    pub fn write_profile(&self, path: &Path, values: &[f64], map_sizes: &[usize], num_players: usize) -> io::Result<()> {
+        let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidData, message);
+
+        let mut root: Value = match fs::read_to_string(path) {
+            Ok(contents) => serde_json::from_str(&contents).map_err(|e| invalid(format!("{} is not valid JSON: {}", path.display(), e)))?,
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => Value::Object(serde_json::Map::new()),
+            Err(e) => return Err(e),
+        };
+        let root_object = root.as_object_mut().ok_or_else(|| invalid(format!("{} does not hold an object", path.display())))?;
+        let profiles = root_object.entry("profiles").or_insert_with(|| Value::Array(Vec::new()))
+            .as_array_mut()
+            .ok_or_else(|| invalid("profiles should be a list".to_string()))?;
+
+        let map_sizes = json!(map_sizes);
+        let players = json!([num_players]);
+        let index = match profiles.iter().position(|profile| profile.get("map_sizes") == Some(&map_sizes) && profile.get("players") == Some(&players)) {
+            Some(index) => index,
+            None => {
+                profiles.push(json!({ "map_sizes": map_sizes, "players": players, "settings": {} }));
+                profiles.len() - 1
+            },
+        };
+        let settings = profiles[index].get_mut("settings").and_then(|settings| settings.as_object_mut())
+            .ok_or_else(|| invalid("every profile needs an object of settings".to_string()))?;
+
+        for (parameter, value) in self.parameters.iter().zip(values) {
+            let value = if parameter.integer { Value::from(*value as i64) } else { Value::from(*value) };
+            settings.insert(parameter.name.to_string(), value);
+        }
+
+        let contents = serde_json::to_string_pretty(&root).map_err(|e| invalid(e.to_string()))?;
+        fs::write(path, contents + "\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hlt::config::StrategyConfig;
+    use std::env;
+    use std::process;
+
+    #[test]
+    fn steps_towards_the_best_score() {
+        let crowding = tunable_parameter("dropoff_crowding").unwrap();
+        let score = |values: &[f64]| -(values[0] - 0.7).powi(2);
+        let mut spsa = Spsa::new(vec![crowding], &[0.2], 100, 5);
+
+        let (plus, minus) = spsa.perturb();
+        spsa.update(score(&plus), score(&minus));
+        let first_step = spsa.values()[0];
+        assert!(first_step > 0.2 && first_step < 0.7, "one step from 0.2 went to {}", first_step);
+
+        for _ in 1..100 {
+            let (plus, minus) = spsa.perturb();
+            spsa.update(score(&plus), score(&minus));
+        }
+        assert!((spsa.values()[0] - 0.7).abs() < 0.02, "ended at {}", spsa.values()[0]);
+    }
+
+    #[test]
+    fn written_profiles_load_back_for_their_sizes_and_players() {
+        let path = env::temp_dir().join(format!("tuning-test-{}.json", process::id()));
+        fs::write(&path, "{ \"rich_halite\": 40 }").unwrap();
+        let parameters = vec![tunable_parameter("return_margin").unwrap(), tunable_parameter("dropoff_crowding").unwrap()];
+        let spsa = Spsa::new(parameters, &[250.0, 0.2], 10, 1);
+
+        spsa.write_profile(&path, &[300.0, 0.35], &[40, 48], 2).unwrap();
+        spsa.write_profile(&path, &[320.0, 0.45], &[40, 48], 2).unwrap();
+
+        let path_name = path.to_str().unwrap();
+        let config = StrategyConfig::load(48, 2, Some(path_name), &[]).unwrap();
+        assert_eq!((config.return_margin, config.dropoff_crowding, config.rich_halite), (320, 0.45, 40));
+        let config = StrategyConfig::load(48, 4, Some(path_name), &[]).unwrap();
+        assert_eq!((config.return_margin, config.rich_halite), (StrategyConfig::for_map(48).return_margin, 40));
+
+        let root: Value = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(root["profiles"].as_array().map(|profiles| profiles.len()), Some(1));
+        fs::remove_file(&path).unwrap();
+    }
+}
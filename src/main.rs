@@ -1,164 +1,359 @@
 extern crate rand;
+extern crate serde_json;
+extern crate zstd;
 
+use hlt::assignment::assign_targets;
+use hlt::clock::Budget;
 use hlt::command::Command;
+use hlt::command::CommandBuffer;
+use hlt::config::StrategyConfig;
+use hlt::direction::Direction;
+use hlt::dropoff_policy::DropoffPolicy;
+use hlt::fleet::Fleet;
+use hlt::fleet::ShipState;
 use hlt::game::Game;
+use hlt::game_map::PathMode;
+use hlt::log::Log;
+use hlt::mining::MiningPlanner;
+use hlt::move_resolver::MoveResolver;
+use hlt::position::Position;
+use hlt::recording::Recording;
+use hlt::return_scheduler::ReturnScheduler;
+use hlt::ship::Ship;
+use hlt::spawn_advisor::SpawnAdvisor;
+use hlt::transport::PipeTransport;
+use hlt::transport::StdioTransport;
+use hlt::transport::Transport;
+use std::cell::RefCell;
 use std::env;
+use std::fs::File;
+use std::io::stdout;
+use std::io::Cursor;
+use std::io::Write;
+use std::process::exit;
 use std::time::SystemTime;
 use std::time::UNIX_EPOCH;
-use std::collections::HashMap;
+use std::collections::HashSet;
+use std::rc::Rc;
 
 mod hlt;
 
+const USAGE: &str = "Usage: my_bot [SEED] [--seed SEED] [--config FILE] [--set NAME=VALUE]... [--record FILE] \
+[--replay FILE [--stop-turn TURN]]";
+
+fn fail(message: &str) -> ! {
+    eprintln!("{}", message);
+    eprintln!("{}", USAGE);
+    exit(1);
+}
+
+// Queues a command, logging it instead if the engine would refuse or ignore it.
+fn queue(command_queue: &mut CommandBuffer, command: Command, log: &Rc<RefCell<Log>>) {
+    if let Err(e) = command_queue.push(command) {
+        log.borrow_mut().log(&format!("Rejected command {}: {}", command, e));
+    }
+}
+
+// The first step of the path to `target`, followed by the other direct moves as fallbacks for
+// when the resolver can't give the ship its first choice. Once the budget is exhausted only the
+// direct moves are given, so every ship still gets a move in time.
+fn directions_towards(game: &Game, ship: &Ship, target: &Position, mode: PathMode, avoided: &HashSet<Position>, budget: &Budget) -> Vec<Direction> {
+    let mut directions = Vec::new();
+    if let Some(path) = game.game_map.find_path(&game.constants, ship, target, mode, avoided, budget) {
+        directions.extend(path.directions.first());
+    }
+    for direction in game.game_map.get_unsafe_moves(&ship.position, target) {
+        if !directions.contains(&direction) {
+            directions.push(direction);
+        }
+    }
+    directions
+}
+
+// Commands that cost next to nothing to work out, sent instead of the planned ones when the turn
+// runs out of time. Ships on their way home take a direct step towards the nearest dropoff when
+// no ship is on that cell and no other ship is stepping onto it; every other ship stays still.
+fn fallback_commands(game: &Game, fleet: &Fleet) -> CommandBuffer {
+    let mut commands = CommandBuffer::new(game);
+    let mut destinations: HashSet<Position> = HashSet::new();
+
+    for ship_id in &game.players[game.my_id.0].ship_ids {
+        if !matches!(fleet.state(*ship_id), ShipState::Returning | ShipState::RushReturn) {
+            continue;
+        }
+
+        let ship = &game.ships[ship_id];
+        if let Some(nearest) = game.game_map.nearest_dropoff(&ship.position, game.my_id) {
+            let destination = game.game_map.normalize(&ship.position.directional_offset(nearest.direction));
+            if nearest.distance > 0 && !game.game_map.at_position(&destination).is_occupied() && !destinations.contains(&destination) &&
+                commands.push(Command::move_ship(*ship_id, nearest.direction)).is_ok()
+            {
+                destinations.insert(destination);
+            }
+        }
+    }
+
+    commands
+}
+
+// Sends the fallback commands if the turn's time is up, before `phase` of planning. Returns whether
+// it did, in which case the turn is over.
+fn out_of_time(game: &Game, budget: &Budget, fallback: &CommandBuffer, phase: &str) -> bool {
+    if !budget.is_exhausted() {
+        return false;
+    }
+
+    game.log.borrow_mut().log(&format!("Out of time before {}, sending the fallback commands.", phase));
+    game.end_turn(fallback.commands());
+    true
+}
+
 fn main() {
     let args: Vec<String> = env::args().collect();
-    let rng_seed: u64 = if args.len() > 1 {
-        args[1].parse().unwrap()
-    } else {
-        SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs()
+    let mut rng_seed: Option<u64> = None;
+    let mut config_path: Option<String> = None;
+    let mut overrides: Vec<String> = Vec::new();
+    let mut record_path: Option<String> = None;
+    let mut replay_path: Option<String> = None;
+    let mut stop_turn: Option<usize> = None;
+
+    let mut i = 1;
+    while i < args.len() {
+        let flag = args[i].as_str();
+        let value = args.get(i + 1).cloned();
+        match flag {
+            "--seed" => rng_seed = Some(value.and_then(|value| value.parse().ok()).unwrap_or_else(|| fail("Expected a number for --seed."))),
+            "--config" => config_path = Some(value.unwrap_or_else(|| fail("Expected a file for --config."))),
+            "--set" => overrides.push(value.unwrap_or_else(|| fail("Expected NAME=VALUE for --set."))),
+            "--record" => record_path = Some(value.unwrap_or_else(|| fail("Expected a file for --record."))),
+            "--replay" => replay_path = Some(value.unwrap_or_else(|| fail("Expected a file for --replay."))),
+            "--stop-turn" => stop_turn = Some(value.and_then(|value| value.parse().ok()).unwrap_or_else(|| fail("Expected a number for --stop-turn."))),
+            // A bare number as the first argument is the seed, as it always was.
+            _ if i == 1 => {
+                rng_seed = Some(flag.parse().unwrap_or_else(|_| fail(&format!("Unknown argument '{}'.", flag))));
+                i += 1;
+                continue;
+            },
+            _ => fail(&format!("Unknown argument '{}'.", flag)),
+        }
+        i += 2;
+    }
+
+    if stop_turn.is_some() && replay_path.is_none() {
+        fail("--stop-turn only applies to --replay.");
+    }
+
+    if replay_path.is_some() && (rng_seed.is_some() || config_path.is_some()) {
+        fail("--replay plays with the seed and config in the recording; change settings with --set.");
+    }
+
+    // A replay reads the engine's lines from a recording made with --record and prints the
+    // commands instead of sending them anywhere. The seed, config and time cutoffs noted in the
+    // recording make it take the same decisions again.
+    let replayed = replay_path.as_ref().map(|path| Recording::open(path).unwrap_or_else(|message| fail(&message)));
+    let transport: Box<dyn Transport> = match &replayed {
+        Some(replayed) => Box::new(PipeTransport::new(Cursor::new(replayed.engine_lines.join("\n")), stdout())),
+        None => Box::new(StdioTransport),
     };
+    let recording = record_path.map(|path| -> Box<dyn Write> {
+        Box::new(File::create(&path).unwrap_or_else(|e| fail(&format!("Couldn't create {}: {}", path, e))))
+    });
 
-    let mut game = Game::new();
+    let mut game = Game::with_recording(transport, recording);
     // At this point "game" variable is populated with initial map data.
     // This is a good place to do computationally expensive start-up pre-processing.
     // As soon as you call "ready" function below, the 2 second per turn timer will start.
-    Game::ready("smarion2-new");
-    let mut ship_status = HashMap::new();
+    let width = game.game_map.width;
+    let num_players = game.players.len();
+    let config = match &replayed {
+        Some(replayed) => replayed.config.as_ref()
+            .ok_or_else(|| "Error: recording: no config noted in the recording.".to_string())
+            .and_then(|recorded| {
+                let mut config = StrategyConfig::for_map(width);
+                config.apply_file(recorded, width, num_players)?;
+                config.apply_overrides(&overrides)?;
+                Ok(config)
+            }),
+        None => StrategyConfig::load(width, num_players, config_path.as_deref(), &overrides),
+    };
+    game.config = match config {
+        Ok(config) => config,
+        Err(message) => game.log.borrow_mut().panic(&message),
+    };
+    let rng_seed = match &replayed {
+        Some(replayed) => replayed.seed.unwrap_or_else(|| game.log.borrow_mut().panic("Error: recording: no seed noted in the recording.")),
+        None => rng_seed.unwrap_or_else(|| SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs()),
+    };
+    game.seed_rng(rng_seed);
+    if let Some(replayed) = replayed {
+        game.clock.replay(replayed.exhausted_polls);
+    }
+    game.note_in_recording(&Recording::seed_note(rng_seed));
+    game.note_in_recording(&Recording::config_note(&game.config.to_json()));
+    game.ready("smarion2-new");
+    let mut fleet = Fleet::new();
+    let transition_log = game.log.clone();
+    fleet.on_transition(move |ship_id, transition| {
+        transition_log.borrow_mut().log(&format!("ship {} went from {:?} to {:?}.", ship_id.0, transition.from, transition.to));
+    });
     game.log.borrow_mut().log(&format!("Successfully created bot! My Player ID is {}. Bot rng seed is {}.", game.my_id.0, rng_seed));
-    let best_dropoff = game.game_map.find_suitable_dropoffs();
-    game.log.borrow_mut().log(&format!("Best drop off found x:{} y:{}.", best_dropoff.x, best_dropoff.y));    
-    
-    let mut building_dropoff = false;
+    game.log.borrow_mut().log(&format!("Strategy config: {}", game.config.to_json()));
+    let mut dropoff_policy = DropoffPolicy::new(&game.config);
+    let spawn_advisor = SpawnAdvisor::new(&game.config);
+    let return_scheduler = ReturnScheduler::new(&game.config);
+    let risk_policy = game.config.risk_policy;
+
     loop {
+        if stop_turn.is_some_and(|stop_turn| game.turn_number >= stop_turn) {
+            game.log.borrow_mut().log(&format!("Replay stopped after turn {}.", game.turn_number));
+            game.log.borrow_mut().flush();
+            return;
+        }
+
         game.update_frame();
-        game.game_map.find_suitable_dropoffs();
+        for ship_id in fleet.update(&game) {
+            let history = &game.history.ships[&ship_id];
+            game.log.borrow_mut().log(&format!("lost ship {} ({:?}) after delivering {} halite.", ship_id.0, history.death_cause, history.delivered));
+        }
+        for ship_id in dropoff_policy.give_up_builds(&game, &fleet) {
+            game.log.borrow_mut().log(&format!("ship {} gave up building a dropoff.", ship_id.0));
+            fleet.transition(ship_id, ShipState::Exploring);
+        }
+
+        // Each step of planning below only starts while there is time left, and the long searches
+        // inside them stop early once it runs out.
+        let turn_budget = game.clock.turn_budget();
+        let fallback = fallback_commands(&game, &fleet);
+
+        if out_of_time(&game, &turn_budget, &fallback, "planning dropoffs") {
+            continue;
+        }
+        if let Some(plan) = dropoff_policy.plan(&game, &fleet, &turn_budget) {
+            game.log.borrow_mut().log(&format!("ship selected dropoff x:{} y:{} for ship {}, costing {} halite.", plan.site.x, plan.site.y, plan.ship_id.0, plan.bank_cost));
+            fleet.transition(plan.ship_id, ShipState::BuildingDropoff(plan.site));
+        }
         let me = &game.players[game.my_id.0];
 
-        let mut command_queue: Vec<Command> = Vec::new();
-        let shipyard = &me.shipyard;  
+        if out_of_time(&game, &turn_budget, &fallback, "planning mining") {
+            continue;
+        }
+        let mut command_queue = CommandBuffer::new(&game);
+        let mut resolver = MoveResolver::new(risk_policy);
+        let planner = MiningPlanner::new(&game);
+        let mut miners = Vec::new();
+        let mut constructing = false;
+        let return_schedule = return_scheduler.schedule(&game);
         for ship_id in &me.ship_ids {
             let ship = &game.ships[ship_id];
-            let id = ship_id.0;
-            let halite = game.game_map.at_entity(ship).halite;
-            if !ship_status.contains_key(&id) {
-                ship_status.insert(id, "exploring".to_string());
-            } else if game.turns_left() <= game.game_map.height - 15 {
-                ship_status.insert(id, "rush_return".to_string());
-            } 
-            if ship_status[&id] == "returning" {
-                let mut closest_drop = game.game_map.calculate_distance(&ship.position, &shipyard.position);
-                let mut closest_pos = shipyard.position;
-                for dropoff_id in &me.dropoff_ids {                    
-                    let dropoff = &game.dropoffs[&dropoff_id];
-                    let dropoff_distance = game.game_map.calculate_distance(&ship.position, &dropoff.position);
-                    if closest_drop > dropoff_distance {
-                        closest_drop = dropoff_distance;
-                        closest_pos = dropoff.position;
-                    }
-                }
-                if &ship.position != &closest_pos {
-                    game.log.borrow_mut().log(&format!("returning ship {}.", id));
-                    let towards_dropoff = &game.game_map.naive_navigate(ship, &closest_pos);
-                    let command = ship.move_ship(*towards_dropoff);
-                    command_queue.push(command);
-                    continue;
-                } else {
-                    ship_status.insert(id, "exploring".to_string());
-                };
+            let risky_cells = game.game_map.risky_cells(ship.halite, risk_policy);
+            if return_scheduler.must_return(&game, &return_schedule, *ship_id) {
+                fleet.transition(*ship_id, ShipState::RushReturn);
             }
-            else if ship_status[&id] == "rush_return" {
-                let mut closest_drop = game.game_map.calculate_distance(&ship.position, &shipyard.position);
-                let mut closest_pos = shipyard.position;
-                for dropoff_id in &me.dropoff_ids {                    
-                    let dropoff = &game.dropoffs[&dropoff_id];
-                    let dropoff_distance = game.game_map.calculate_distance(&ship.position, &dropoff.position);
-                    if closest_drop > dropoff_distance {
-                        closest_drop = dropoff_distance;
-                        closest_pos = dropoff.position;
-                    }
-                }
-                let towards_closest = game.game_map.get_unsafe_moves(&ship.position, &closest_pos);                
-                for moves in towards_closest {
-                    let command = ship.move_ship(moves);
-                    command_queue.push(command);
-                    break;
-                }
-                continue;
-            } else if ship_status[&id].contains("dropoff") {
-                let drop_id = &ship_status[&id][7..];
-                let drop_pos = best_dropoffs[drop_id.parse::<usize>().unwrap()];
-                game.log.borrow_mut().log(&format!("ship turning into dropoff: {} for ship {}.", drop_id, id));
-                if &drop_pos != &ship.position && me.halite >= game.constants.dropoff_cost {
-                    let towards_dropoff = &game.game_map.naive_navigate(ship, &drop_pos);
-                    let command = ship.move_ship(*towards_dropoff);
-                    command_queue.push(command);
-                } else {
-                    let command = if me.halite >= game.constants.dropoff_cost {
-                        building_dropoff = false;
-                        ship.make_dropoff()
+
+            match fleet.state(*ship_id) {
+                ShipState::Returning => {
+                    let nearest = game.game_map.nearest_dropoff(&ship.position, game.my_id).unwrap();
+                    let closest_pos = nearest.position;
+                    if ship.position != closest_pos {
+                        // Head along the cheapest path home.
+                        let directions = directions_towards(&game, ship, &closest_pos, PathMode::LeastHalite, &risky_cells, &turn_budget);
+                        resolver.request(ship, &directions, game.config.returning_priority);
+                        continue;
                     } else {
-                        ship.stay_still()
+                        fleet.transition(*ship_id, ShipState::Exploring);
                     };
-                    command_queue.push(command);
-                }
-                continue;
-
-            } else if ship.halite >= game.constants.max_halite - 250 {
-                ship_status.insert(id, "returning".to_string());
-            }          
-
-            let command = if halite < 10 || ship.is_full() {                
-                let best_direction = game.game_map.most_halite_near_ship_direction(&ship.position);
-                match best_direction {
-                    Some(x) => {
-                        let safe_pos = &game.game_map.naive_navigate(ship, &ship.position.directional_offset(x));
-                        ship.move_ship(*safe_pos)
-                    },
-                    None => {                        
-                        let random_direction = game.game_map.move_towards_rich_halite(&ship.position);
-                        game.log.borrow_mut().log(&format!("best direction: {:?} found for ship {}.", random_direction, ship.id.0));
-                        let safe_pos = &game.game_map.naive_navigate(ship, &ship.position.directional_offset(random_direction));
-                        ship.move_ship(*safe_pos)
+                },
+                ShipState::RushReturn => {
+                    let nearest = game.game_map.nearest_dropoff(&ship.position, game.my_id).unwrap();
+                    let closest_pos = nearest.position;
+                    // This is the last trip home, so ships may pile up on the dropoff they deliver to.
+                    resolver.allow_collisions_at(&game.game_map, &closest_pos);
+                    let mut directions = vec![nearest.direction];
+                    for direction in game.game_map.get_unsafe_moves(&ship.position, &closest_pos) {
+                        if direction != nearest.direction {
+                            directions.push(direction);
+                        }
+                    }
+                    resolver.request(ship, &directions, game.config.rush_priority);
+                    continue;
+                },
+                ShipState::BuildingDropoff(drop_pos) => {
+                    if game.game_map.at_position(&drop_pos).has_structure() {
+                        // Someone built on the site before we got there, so pick another one later.
+                        fleet.transition(*ship_id, ShipState::Exploring);
+                    } else if drop_pos != ship.position {
+                        // Travel while the bank saves up, so the build can happen on arrival.
+                        let directions = directions_towards(&game, ship, &drop_pos, PathMode::FewestTurns, &risky_cells, &turn_budget);
+                        resolver.request(ship, &directions, game.config.builder_priority);
+                        continue;
+                    } else if command_queue.push(ship.make_dropoff()).is_ok() {
+                        game.log.borrow_mut().log(&format!("ship {} turning into dropoff at x:{} y:{}.", ship_id.0, drop_pos.x, drop_pos.y));
+                        constructing = true;
+                        continue;
+                    } else {
+                        // Not enough halite yet. Waiting on the site still mines it.
+                        resolver.request(ship, &[], game.config.builder_priority);
+                        continue;
                     }
+                },
+                ShipState::Exploring => {
+                    if ship.halite >= game.constants.max_halite.saturating_sub(game.config.return_margin) {
+                        fleet.transition(*ship_id, ShipState::Returning);
+                    }
+                },
+            }
+
+            miners.push(ship);
+        }
+
+        // Miners share out the best cells so no two of them chase the same one.
+        // Whatever time assignment doesn't get is left for paths and resolving moves.
+        let targets = assign_targets(&game, &planner, &miners, &game.clock.budget(game.config.assignment_time_share));
+        for ship in miners {
+            let risky_cells = game.game_map.risky_cells(ship.halite, risk_policy);
+            fleet.set_target(ship.id, targets.get(&ship.id).map(|target| target.position));
+            let directions = match targets.get(&ship.id) {
+                Some(target) if target.position == ship.position => Vec::new(),
+                Some(target) => directions_towards(&game, ship, &target.position, PathMode::FewestTurns, &risky_cells, &turn_budget),
+                None => {                        
+                    let random_direction = game.game_map.move_towards_rich_halite(&ship.position, game.config.rich_halite, &mut game.rng.borrow_mut());
+                    game.log.borrow_mut().log(&format!("best direction: {:?} found for ship {}.", random_direction, ship.id.0));
+                    vec![random_direction]
                 }
-            } else {
-                ship.stay_still()
             };
-            command_queue.push(command);
+            resolver.request(ship, &directions, game.config.miner_priority);
         }
 
-        let shipyard_cell = game.game_map.at_entity(&me.shipyard);
-
-        if game.turn_number == 60 //&&
-           //me.halite < game.constants.dropoff_cost
-        {
-            building_dropoff = true;
-            let mut best_ship = 0;
-            let mut best_dropoff = 0;
-            let mut min_distance = 0;
-            for ship_id in &me.ship_ids {
-                let ship = &game.ships[ship_id];
-                for i in 0..best_dropoffs.len() {
-                    let distance = game.game_map.calculate_distance(&ship.position, &best_dropoffs[i]);
-                    if  distance < min_distance ||
-                       min_distance == 0 {
-                           best_ship = ship.id.0;
-                           best_dropoff = i;
-                           min_distance = distance;
-                       }
-                }
-            }
-            game.log.borrow_mut().log(&format!("ship selected dropoff: {} found for ship {}.", best_dropoff, best_ship));
-            ship_status.insert(best_ship, "dropoff".to_string() + &best_dropoff.to_string());
+        if out_of_time(&game, &turn_budget, &fallback, "resolving moves") {
+            continue;
         }
+        let moves = resolver.resolve(&game.game_map, &game.constants);
+        for ship_move in &moves {
+            queue(&mut command_queue, ship_move.command(), &game.log);
+        }
+
+        // The shipyard is free for a new ship if none of ours ends up there and no enemy sits on it.
+        let shipyard_position = game.game_map.normalize(&me.shipyard.position);
+        let shipyard_free = !moves.iter().any(|ship_move| ship_move.destination == shipyard_position) &&
+            match game.game_map.at_position(&shipyard_position).ship {
+                Some(ship_id) => moves.iter().any(|ship_move| ship_move.ship_id == ship_id),
+                None => true,
+            };
 
-        if game.turn_number <= 200 &&
-           me.halite >= game.constants.ship_cost &&
-           !shipyard_cell.is_occupied() &&
-           !building_dropoff
+        // Keep what a planned dropoff will cost in the bank, but spawn with whatever is left over.
+        // A dropoff built this turn has already been paid for by the command queue.
+        if out_of_time(&game, &turn_budget, &fallback, "deciding whether to spawn") {
+            continue;
+        }
+        let reserve = if constructing { 0 } else { dropoff_policy.reserve(&game, &fleet) };
+        if command_queue.available_halite() >= game.constants.ship_cost + reserve &&
+           shipyard_free &&
+           spawn_advisor.should_spawn(&game)
         {
-            command_queue.push(me.shipyard.spawn());
+            game.log.borrow_mut().log(&format!("spawning, a new ship should return {:.0} halite.", spawn_advisor.expected_return(&game)));
+            queue(&mut command_queue, me.shipyard.spawn(), &game.log);
         }
 
-        Game::end_turn(&command_queue);
+        game.end_turn(command_queue.commands());
     }
 }
@@ -0,0 +1,128 @@
+extern crate rand;
+extern crate serde_json;
+extern crate zstd;
+
+use hlt::match_runner::MatchSettings;
+use hlt::match_runner::Tournament;
+use hlt::match_runner::play_match;
+use std::env;
+use std::path::PathBuf;
+use std::process::exit;
+use std::time::Duration;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+
+mod hlt;
+
+const USAGE: &str = "Usage: runner [--games N] [--seed SEED] [--sizes 32,40,...] [--players 2|4] \
+[--replay-directory DIR] [--turn-timeout MS] [--init-timeout MS] \"bot command\" \"bot command\" ...";
+
+fn fail(message: &str) -> ! {
+    eprintln!("{}", message);
+    eprintln!("{}", USAGE);
+    exit(1);
+}
+
+fn parse_value<T: std::str::FromStr>(flag: &str, value: Option<&String>) -> T {
+    match value.map(|value| value.parse()) {
+        Some(Ok(value)) => value,
+        _ => fail(&format!("Expected a value for {}.", flag)),
+    }
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+
+    let mut games: usize = 1;
+    let mut seed: u64 = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+    let mut sizes: Vec<usize> = vec![32];
+    let mut num_players: Option<usize> = None;
+    let mut replay_directory: Option<PathBuf> = None;
+    let mut turn_timeout_ms: u64 = 2000;
+    let mut init_timeout_ms: u64 = 30000;
+    let mut bot_commands: Vec<String> = Vec::new();
+
+    let mut i = 1;
+    while i < args.len() {
+        let flag = args[i].as_str();
+        let value = args.get(i + 1);
+        match flag {
+            "--games" => games = parse_value(flag, value),
+            "--seed" => seed = parse_value(flag, value),
+            "--players" => num_players = Some(parse_value(flag, value)),
+            "--turn-timeout" => turn_timeout_ms = parse_value(flag, value),
+            "--init-timeout" => init_timeout_ms = parse_value(flag, value),
+            "--replay-directory" => replay_directory = Some(PathBuf::from(parse_value::<String>(flag, value))),
+            "--sizes" => {
+                let list: String = parse_value(flag, value);
+                sizes = list.split(',').map(|size| match size.trim().parse::<usize>() {
+                    Ok(size) if (32..=64).contains(&size) && size.is_multiple_of(2) => size,
+                    Ok(_) => fail(&format!("Invalid map size '{}', sizes must be even and between 32 and 64.", size)),
+                    Err(_) => fail(&format!("Invalid map size '{}'.", size)),
+                }).collect();
+            },
+            "--help" | "-h" => {
+                println!("{}", USAGE);
+                return;
+            },
+            _ if flag.starts_with("--") => fail(&format!("Unknown flag {}.", flag)),
+            _ => {
+                bot_commands.push(flag.to_string());
+                i += 1;
+                continue;
+            },
+        }
+        i += 2;
+    }
+
+    if bot_commands.is_empty() {
+        fail("No bot commands given.");
+    }
+
+    let num_players = num_players.unwrap_or(if bot_commands.len() > 2 { 4 } else { 2 });
+    if num_players != 2 && num_players != 4 {
+        fail("Games must have 2 or 4 players.");
+    }
+    // Every seat needs its own command, so a bot only plays itself when it is listed twice.
+    if bot_commands.len() != num_players {
+        fail(&format!("Got {} bots for {} player games, give one command per seat.", bot_commands.len(), num_players));
+    }
+    if let Some(directory) = &replay_directory {
+        if let Err(e) = std::fs::create_dir_all(directory) {
+            fail(&format!("Couldn't create replay directory {}: {}", directory.display(), e));
+        }
+    }
+
+    let mut tournament = Tournament::new(&bot_commands);
+    for game in 0..games {
+        let size = sizes[game % sizes.len()];
+        let settings = MatchSettings {
+            seed: seed + game as u64,
+            width: size,
+            height: size,
+            init_timeout: Duration::from_millis(init_timeout_ms),
+            turn_timeout: Duration::from_millis(turn_timeout_ms),
+            replay_directory: replay_directory.clone(),
+        };
+
+        // Rotate the seats every game so no bot always gets the same corner of the map.
+        let seats: Vec<usize> = (0..num_players).map(|seat| (seat + game) % num_players).collect();
+        let seated_commands: Vec<String> = seats.iter().map(|bot| bot_commands[*bot].clone()).collect();
+
+        let result = match play_match(&seated_commands, &settings) {
+            Ok(result) => result,
+            Err(e) => fail(&format!("Game {} failed: {}", game + 1, e)),
+        };
+
+        let standings: Vec<String> = result.rankings.iter()
+            .map(|player_id| format!("{} ({})", result.names[player_id.0], result.halite[player_id.0]))
+            .collect();
+        println!("Game {}/{} seed {} {}x{} turns {}: {}",
+            game + 1, games, settings.seed, size, size, result.turns, standings.join(", "));
+
+        tournament.record(&seats, &result);
+    }
+
+    println!();
+    print!("{}", tournament.report());
+}
@@ -0,0 +1,209 @@
+extern crate rand;
+extern crate serde_json;
+extern crate zstd;
+
+use hlt::config::StrategyConfig;
+use hlt::match_runner::MatchResult;
+use hlt::match_runner::MatchSettings;
+use hlt::match_runner::play_match;
+use hlt::tuning::Spsa;
+use hlt::tuning::TUNABLE_PARAMETERS;
+use hlt::tuning::tunable_parameter;
+use std::env;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process::exit;
+use std::thread;
+use std::time::Duration;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+
+mod hlt;
+
+const USAGE: &str = "Usage: tuner --bot \"bot command\" [--opponent \"bot command\"] [--iterations N] [--games N] \
+[--jobs N] [--seed SEED] [--sizes 32,40,...] [--players 2|4] [--params name,name,...] [--config FILE] \
+[--output FILE] [--turn-timeout MS] [--init-timeout MS]";
+
+fn fail(message: &str) -> ! {
+    eprintln!("{}", message);
+    eprintln!("{}", USAGE);
+    exit(1);
+}
+
+fn parse_value<T: std::str::FromStr>(flag: &str, value: Option<&String>) -> T {
+    match value.map(|value| value.parse()) {
+        Some(Ok(value)) => value,
+        _ => fail(&format!("Expected a value for {}.", flag)),
+    }
+}
+
+// Plays every game, `jobs` at a time, stopping the tuner if one can't be played at all.
+fn play_all(games: &[(Vec<String>, MatchSettings)], jobs: usize) -> Vec<MatchResult> {
+    let mut results = Vec::with_capacity(games.len());
+    for batch in games.chunks(jobs.max(1)) {
+        let batch_results: Vec<_> = thread::scope(|scope| {
+            let handles: Vec<_> = batch.iter()
+                .map(|(commands, settings)| scope.spawn(move || play_match(commands, settings)))
+                .collect();
+            handles.into_iter().map(|handle| handle.join().expect("Error: tuner: game thread panicked.")).collect()
+        });
+        for result in batch_results {
+            match result {
+                Ok(result) => results.push(result),
+                Err(e) => fail(&format!("A game failed: {}", e)),
+            }
+        }
+    }
+    results
+}
+
+// The tuned bot's share of all the halite collected in a game.
+fn halite_share(result: &MatchResult, seat: usize) -> f64 {
+    let total: usize = result.halite.iter().sum();
+    if total == 0 {
+        return 1.0 / result.halite.len() as f64;
+    }
+    result.halite[seat] as f64 / total as f64
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+
+    let mut bot: Option<String> = None;
+    let mut opponent: Option<String> = None;
+    let mut iterations: usize = 50;
+    let mut games: usize = 4;
+    let mut jobs: usize = 1;
+    let mut seed: u64 = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+    let mut sizes: Vec<usize> = vec![32];
+    let mut num_players: usize = 2;
+    let mut parameter_names: Option<Vec<String>> = None;
+    let mut config_path: Option<String> = None;
+    let mut output: Option<PathBuf> = None;
+    let mut turn_timeout_ms: u64 = 2000;
+    let mut init_timeout_ms: u64 = 30000;
+
+    let mut i = 1;
+    while i < args.len() {
+        let flag = args[i].as_str();
+        let value = args.get(i + 1);
+        match flag {
+            "--bot" => bot = Some(parse_value(flag, value)),
+            "--opponent" => opponent = Some(parse_value(flag, value)),
+            "--iterations" => iterations = parse_value(flag, value),
+            "--games" => games = parse_value(flag, value),
+            "--jobs" => jobs = parse_value(flag, value),
+            "--seed" => seed = parse_value(flag, value),
+            "--players" => num_players = parse_value(flag, value),
+            "--config" => config_path = Some(parse_value(flag, value)),
+            "--output" => output = Some(PathBuf::from(parse_value::<String>(flag, value))),
+            "--turn-timeout" => turn_timeout_ms = parse_value(flag, value),
+            "--init-timeout" => init_timeout_ms = parse_value(flag, value),
+            "--params" => {
+                let list: String = parse_value(flag, value);
+                parameter_names = Some(list.split(',').map(|name| name.trim().to_string()).collect());
+            },
+            "--sizes" => {
+                let list: String = parse_value(flag, value);
+                sizes = list.split(',').map(|size| match size.trim().parse() {
+                    Ok(size) => size,
+                    Err(_) => fail(&format!("Invalid map size '{}'.", size)),
+                }).collect();
+            },
+            "--help" | "-h" => {
+                println!("{}", USAGE);
+                return;
+            },
+            _ => fail(&format!("Unknown flag {}.", flag)),
+        }
+        i += 2;
+    }
+
+    let bot = bot.unwrap_or_else(|| fail("No bot command given."));
+    if num_players != 2 && num_players != 4 {
+        fail("Games must have 2 or 4 players.");
+    }
+    if games == 0 || sizes.is_empty() {
+        fail("Need at least one game and one map size per evaluation.");
+    }
+
+    let parameters: Vec<_> = match parameter_names {
+        Some(names) => names.iter()
+            .map(|name| tunable_parameter(name).unwrap_or_else(|| fail(&format!("Can't tune '{}'.", name))))
+            .collect(),
+        None => TUNABLE_PARAMETERS.iter().collect(),
+    };
+
+    // Tuning starts from what the bot would play with on the first map size.
+    let base_config = match StrategyConfig::load(sizes[0], num_players, config_path.as_deref(), &[]) {
+        Ok(config) => config.to_json(),
+        Err(message) => fail(&message),
+    };
+    let start: Vec<f64> = parameters.iter()
+        .map(|parameter| base_config[parameter.name].as_f64().unwrap_or_else(|| fail(&format!("No value for {}.", parameter.name))))
+        .collect();
+
+    let base_command = match &config_path {
+        Some(path) => format!("{} --config {}", bot, path),
+        None => bot.clone(),
+    };
+    let opponent = opponent.unwrap_or_else(|| base_command.clone());
+    // The opponent reads the config file every game, so writing the profile into it would have the
+    // bot play against a moving target.
+    let output = output.unwrap_or_else(|| PathBuf::from("tuned_strategy.json"));
+    if config_path.as_ref().is_some_and(|path| Path::new(path) == output) {
+        fail("--output can't be the --config file, which the games are played with.");
+    }
+
+    let mut spsa = Spsa::new(parameters, &start, iterations, seed);
+    for iteration in 0..iterations {
+        let (plus, minus) = spsa.perturb();
+        let command = |values: &[f64]| -> String {
+            let settings: Vec<String> = spsa.settings(values).iter().map(|setting| format!("--set {}", setting)).collect();
+            format!("{} {}", base_command, settings.join(" "))
+        };
+        let plus_command = command(&plus);
+        let minus_command = command(&minus);
+
+        // Both sides play the same maps from the same seats, so map luck cancels out.
+        let mut matches = Vec::with_capacity(2 * games);
+        let mut seats = Vec::with_capacity(2 * games);
+        for side_command in &[&plus_command, &minus_command] {
+            for game in 0..games {
+                let size = sizes[game % sizes.len()];
+                let seat = game % num_players;
+                let mut commands = vec![opponent.clone(); num_players];
+                commands[seat] = side_command.to_string();
+                let settings = MatchSettings {
+                    seed: seed + (iteration * games + game) as u64,
+                    width: size,
+                    height: size,
+                    init_timeout: Duration::from_millis(init_timeout_ms),
+                    turn_timeout: Duration::from_millis(turn_timeout_ms),
+                    replay_directory: None,
+                };
+                matches.push((commands, settings));
+                seats.push(seat);
+            }
+        }
+
+        let results = play_all(&matches, jobs);
+        let shares: Vec<f64> = results.iter().zip(&seats).map(|(result, seat)| halite_share(result, *seat)).collect();
+        let plus_score = shares[..games].iter().sum::<f64>() / games as f64;
+        let minus_score = shares[games..].iter().sum::<f64>() / games as f64;
+
+        spsa.update(plus_score, minus_score);
+        println!("Iteration {}/{}: plus {:.3}, minus {:.3}. Now {}",
+            spsa.iteration(), iterations, plus_score, minus_score, spsa.settings(&spsa.values()).join(" "));
+
+        // The values the optimizer has settled on are written, not the side that happened to score
+        // best: with a few games per side the highest score is mostly luck. Writing after every
+        // iteration means stopping the tuner early keeps what it has found.
+        if let Err(e) = spsa.write_profile(&output, &spsa.values(), &sizes, num_players) {
+            fail(&format!("Couldn't write {}: {}", output.display(), e));
+        }
+    }
+
+    println!();
+    println!("Wrote the tuned profile for sizes {:?} and {} players to {}.", sizes, num_players, output.display());
+}